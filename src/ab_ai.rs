@@ -1,17 +1,33 @@
-use std::collections::HashMap;
-use crate::board::{Board, Color};
+use std::time::{Duration, Instant};
+use crate::board::{Board, Color, Piece, Undo};
+use crate::evaluator::{Evaluator, Score};
 use crate::mv::Move;
+use crate::nnue::AccumulatorStack;
+use crate::search::{SearchLimits, SearchResult};
+use crate::tt::{Bound, Entry, TranspositionTable};
+use crate::weights::EvalWeights;
 
 // Eval bar
 pub fn bar(board: &Board) -> f32 {
+    (bar_with(board, &EvalWeights::default()) * 100.0).round() / 100.0
+}
+
+// Eval bar with explicit weights (unrounded so the tuner sees small changes)
+pub fn bar_with(board: &Board, weights: &EvalWeights) -> f32 {
+    if board.rules().reversed_goal() {
+        return giveaway_bar(board, weights);
+    }
+
     let mut bar: f32 = 0.0;
+    let last = (board.rules().size() - 1) as f32;
+    let middle = last / 2.0;
 
     // Iterate through all board squares
-    for i in 0..32 {
+    for i in 0..board.num_squares() {
         let piece = board.squares[i];
 
         // Skip empty squares
-        if piece == '□' {
+        if piece.is_empty() {
             continue;
         }
 
@@ -20,18 +36,18 @@ pub fn bar(board: &Board) -> f32 {
 
         // Base piece values
         let base_value = match piece {
-            'r' => 1.0,   // Red pawn
-            'R' => 2.5,   // Red king
-            'b' => -1.0,  // Black pawn
-            'B' => -2.5,  // Black king
+            Piece::RedMan => weights.pawn,
+            Piece::RedKing => weights.king,
+            Piece::BlackMan => -weights.pawn,
+            Piece::BlackKing => -weights.king,
             _ => 0.0
         };
 
         // Columns A/H are less valuable
-        let edge_penalty = if col == 0 || col == 7 {
+        let edge_penalty = if col == 0 || col as f32 == last {
             match piece {
-                'r' | 'R' => -0.25,
-                'b' | 'B' => 0.25,
+                Piece::RedMan | Piece::RedKing => -weights.edge,
+                Piece::BlackMan | Piece::BlackKing => weights.edge,
                 _ => 0.0
             }
         } else {
@@ -40,38 +56,208 @@ pub fn bar(board: &Board) -> f32 {
 
         let row_bonus = match piece {
 
-            'r' => {
-                if row == 0 { 2.5 }
-                else { (7.0 - row as f32) / 7.0 * 0.5}
+            Piece::RedMan => {
+                if row == 0 { weights.promotion }
+                else { (last - row as f32) / last * weights.advance }
             },
 
-            'b' => {
-                if row == 7{ -2.5 }
-                else { -(row as f32) / 7.0 * 0.5 }
+            Piece::BlackMan => {
+                if row as f32 == last { -weights.promotion }
+                else { -(row as f32) / last * weights.advance }
             },
             // Kings want to stay in the middle
-            'R' => 1.5 * (1.0 - (3.5 - row as f32).abs() / 3.5),
-            'B' => -1.5 * (1.0 - (3.5 - row as f32).abs() / 3.5),
+            Piece::RedKing => weights.king_center * (1.0 - (middle - row as f32).abs() / middle),
+            Piece::BlackKing => -weights.king_center * (1.0 - (middle - row as f32).abs() / middle),
             _ => 0.0
         };
 
         bar += base_value + row_bonus + edge_penalty;    }
 
-    (bar * 100.0).round() / 100.0
+    bar + positional(board, weights)
+}
+
+// Giveaway scoring, from Red's point of view: material is a burden, kings
+// most of all since they are hard to get rid of, and a piece the opponent
+// can take is an asset
+fn giveaway_bar(board: &Board, weights: &EvalWeights) -> f32 {
+    (0..board.num_squares())
+        .filter_map(|i| {
+            let piece = board.squares[i];
+            let color = piece.color()?;
+            let sign = match color {
+                Color::Red => 1.0,
+                Color::Black => -1.0,
+            };
+            let burden = if piece.is_king() { weights.king } else { weights.pawn };
+            let offered = if is_exposed(board, i, color) { weights.exposed } else { 0.0 };
+            Some(sign * (offered - burden))
+        })
+        .sum()
+}
+
+// Diagonal neighbour of a square, if it is on the board
+fn step(board: &Board, row: usize, col: usize, dr: i32, dc: i32) -> Option<usize> {
+    board.coords_to_index(row as i32 + dr, col as i32 + dc)
+}
+
+// Row direction a man of this color moves in
+fn forward(color: Color) -> i32 {
+    match color {
+        Color::Red => -1,
+        Color::Black => 1,
+    }
+}
+
+// Directions a piece may move and capture in
+fn piece_dirs(piece: Piece, color: Color) -> &'static [(i32, i32)] {
+    match (piece.is_king(), color) {
+        (true, _) => &[(-1, -1), (-1, 1), (1, -1), (1, 1)],
+        (false, Color::Red) => &[(-1, -1), (-1, 1)],
+        (false, Color::Black) => &[(1, -1), (1, 1)],
+    }
 }
 
-pub fn ab_ai(board: &Board, depth: u32) -> Option<Move> {
+// A man is a runaway when every square in the cone in front of it is empty,
+// so nothing can stop it from crowning
+fn is_runaway(board: &Board, row: usize, col: usize, color: Color) -> bool {
+    let rows_left = match color {
+        Color::Red => row,
+        Color::Black => board.rules().size() - 1 - row,
+    } as i32;
+
+    (1..=rows_left).all(|k| {
+        (-k..=k).step_by(2).all(|dc| {
+            step(board, row, col, forward(color) * k, dc)
+                .map(|idx| board.squares[idx].is_empty())
+                .unwrap_or(true)
+        })
+    })
+}
+
+// Whether the opponent could jump the piece on this square right now
+fn is_exposed(board: &Board, index: usize, color: Color) -> bool {
+    let (row, col) = board.index_to_coords(index);
+
+    [(-1, -1), (-1, 1), (1, -1), (1, 1)].iter().any(|&(dr, dc)| {
+        let attacker = step(board, row, col, dr, dc).map(|idx| board.squares[idx]);
+        let landing = step(board, row, col, -dr, -dc).map(|idx| board.squares[idx]);
+
+        match (attacker, landing) {
+            (Some(piece), Some(Piece::Empty)) if piece.color() == Some(color.toggle()) => {
+                // The attacker jumps in the (-dr, -dc) direction
+                piece_dirs(piece, color.toggle()).contains(&(-dr, -dc))
+            },
+            _ => false,
+        }
+    })
+}
+
+// Optional positional terms, scored from Red's point of view
+fn positional(board: &Board, weights: &EvalWeights) -> f32 {
+    let features = &weights.features;
+    let mut score = 0.0;
+    let rules = board.rules();
+
+    for i in 0..board.num_squares() {
+        let piece = board.squares[i];
+        let Some(color) = piece.color() else { continue };
+
+        let sign = match color {
+            Color::Red => 1.0,
+            Color::Black => -1.0,
+        };
+        let (row, col) = board.index_to_coords(i);
+        let king = piece.is_king();
+
+        // Squares the piece can step to without capturing
+        let steps = piece_dirs(piece, color).iter()
+            .filter_map(|&(dr, dc)| step(board, row, col, dr, dc))
+            .filter(|&idx| board.squares[idx].is_empty())
+            .count();
+
+        if features.back_rank && !king && rules.bridge_squares(color).contains(&i) {
+            score += sign * weights.back_rank;
+        }
+
+        if features.runaway && !king && is_runaway(board, row, col, color) {
+            score += sign * weights.runaway;
+        }
+
+        if features.mobility {
+            score += sign * weights.mobility * steps as f32;
+        }
+
+        if features.trapped_king && king && steps == 0 {
+            let can_jump = piece_dirs(piece, color).iter().any(|&(dr, dc)| {
+                let over = step(board, row, col, dr, dc).map(|idx| board.squares[idx]);
+                let land = step(board, row, col, 2 * dr, 2 * dc).map(|idx| board.squares[idx]);
+                matches!((over.and_then(Piece::color), land), (Some(c), Some(Piece::Empty)) if c != color)
+            });
+
+            if !can_jump {
+                score -= sign * weights.trapped_king;
+            }
+        }
+
+        // Tempo: rows advanced from the man's own back rank
+        if features.tempo && !king {
+            let advanced = match color {
+                Color::Red => board.rules().size() - 1 - row,
+                Color::Black => row,
+            };
+            score += sign * weights.tempo * advanced as f32;
+        }
+
+        if features.dog_hole && !king {
+            let stuck = rules.dog_hole(color)
+                .is_some_and(|(hole, holder)| i == hole && board.squares[holder].color() == Some(color.toggle()));
+            if stuck {
+                score -= sign * weights.dog_hole;
+            }
+        }
+
+        if features.exposed && is_exposed(board, i, color) {
+            score -= sign * weights.exposed;
+        }
+    }
+
+    // The move: in an even endgame, the side to move has the opposition when
+    // the number of pieces on its system columns (the columns of its own back
+    // rank) is odd
+    let total = board.red_pieces + board.black_pieces;
+    if features.opposition && board.red_pieces == board.black_pieces && total <= 8 {
+        let back_rank = match board.turn {
+            Color::Red => board.num_squares() - 1,
+            Color::Black => 0,
+        };
+        let system_col_parity = board.index_to_coords(back_rank).1 % 2;
+        let in_system = (0..board.num_squares())
+            .filter(|&i| !board.squares[i].is_empty())
+            .filter(|&i| board.index_to_coords(i).1 % 2 == system_col_parity)
+            .count();
+
+        let sign = match board.turn {
+            Color::Red => 1.0,
+            Color::Black => -1.0,
+        };
+        if in_system % 2 == 1 {
+            score += sign * weights.opposition;
+        } else {
+            score -= sign * weights.opposition;
+        }
+    }
+
+    score
+}
+
+pub fn ab_ai<E: Evaluator + ?Sized>(board: &Board, depth: u32, evaluator: &E) -> Option<Move> {
     let valid_moves = board.get_valid_moves();
 
     if valid_moves.is_empty() {
         return None;
     }
 
-    let mut move_values: HashMap<String, f32> = HashMap::new();
-
-    let is_maximizing = board.turn == Color::Red;
-
-    let (value, best_move) = minimax_ab(board, depth, is_maximizing, f32::NEG_INFINITY, f32::INFINITY);
+    let (value, best_move) = minimax_ab(board, depth, f32::NEG_INFINITY, f32::INFINITY, evaluator);
 
     let best_move = best_move.unwrap_or_else(|| valid_moves[0].clone());
 
@@ -80,80 +266,287 @@ pub fn ab_ai(board: &Board, depth: u32) -> Option<Move> {
     Some(best_move)
 }
 
-fn minimax_ab(board: &Board, depth: u32, is_maximizing_player: bool, mut alpha: f32, mut beta: f32) -> (f32, Option<Move>) {
+// Bookkeeping shared by every node of one search
+struct SearchContext<'a, E: ?Sized> {
+    evaluator: &'a E,
+    limits: &'a SearchLimits,
+    tt: Option<&'a mut TranspositionTable>,
+    start: Instant,
+    nodes: u64,
+    stopped: bool,
+    move_lists: Vec<Vec<Move>>,  // One per ply, reused from node to node
+    pv: Vec<Vec<Move>>,          // Best line found below each ply, reused the same way
+    accumulators: Option<AccumulatorStack<'a>>,  // Kept in step with the board for network evaluators
+}
+
+impl<'a, E: Evaluator + ?Sized> SearchContext<'a, E> {
+    fn new(evaluator: &'a E, limits: &'a SearchLimits, tt: Option<&'a mut TranspositionTable>, root: &Board) -> Self {
+        SearchContext {
+            evaluator,
+            limits,
+            tt: tt.filter(|tt| tt.is_enabled()),
+            start: Instant::now(),
+            nodes: 0,
+            stopped: false,
+            move_lists: Vec::new(),
+            pv: Vec::new(),
+            accumulators: evaluator.network(root).map(|network| AccumulatorStack::new(network, root)),
+        }
+    }
+
+    fn evaluate(&self, board: &Board) -> Score {
+        match &self.accumulators {
+            Some(stack) => stack.evaluate(board),
+            None => self.evaluator.evaluate(board),
+        }
+    }
+
+    // Board::make_move, keeping the accumulators in step
+    fn make_move(&mut self, board: &mut Board, mv: &Move) -> Option<Undo> {
+        let undo = board.make_move(mv).ok()?;
+        if let Some(stack) = &mut self.accumulators {
+            stack.make_move(board, mv, &undo);
+        }
+        Some(undo)
+    }
+
+    fn unmake_move(&mut self, board: &mut Board, mv: &Move, undo: Undo) {
+        if let Some(stack) = &mut self.accumulators {
+            stack.unmake_move();
+        }
+        board.unmake_move(mv, undo);
+    }
+
+    fn take_move_list(&mut self, ply: u32) -> Vec<Move> {
+        self.move_lists.get_mut(ply as usize).map(std::mem::take).unwrap_or_default()
+    }
+
+    fn return_move_list(&mut self, ply: u32, mut moves: Vec<Move>) {
+        let ply = ply as usize;
+        if self.move_lists.len() <= ply {
+            self.move_lists.resize_with(ply + 1, Vec::new);
+        }
+        moves.clear();
+        self.move_lists[ply] = moves;
+    }
+
+    // Start the line at `ply` over, empty until a move improves on it
+    fn clear_pv(&mut self, ply: u32) {
+        let ply = ply as usize;
+        if self.pv.len() <= ply + 1 {
+            self.pv.resize_with(ply + 2, Vec::new);
+        }
+        self.pv[ply].clear();
+    }
+
+    // The line at `ply` becomes mv followed by the line just found at ply + 1
+    fn update_pv(&mut self, ply: u32, mv: &Move) {
+        let (line, below) = self.pv.split_at_mut(ply as usize + 1);
+        let line = &mut line[ply as usize];
+        line.clear();
+        line.push(mv.clone());
+        line.extend_from_slice(&below[0]);
+    }
+
+    // The clock and stop handle are only checked every 1024 nodes
+    fn should_stop(&mut self) -> bool {
+        if !self.stopped {
+            let out_of_nodes = self.limits.nodes.is_some_and(|limit| self.nodes >= limit);
+            let expired = self.nodes.is_multiple_of(1024) && self.limits.expired(self.start);
+            self.stopped = out_of_nodes || expired;
+        }
+        self.stopped
+    }
+}
+
+// Negamax with alpha-beta pruning. Scores are relative to the side to move,
+// so every level maximizes and a child's score is negated on the way up.
+pub fn minimax_ab<E: Evaluator + ?Sized>(board: &Board, depth: u32, alpha: Score, beta: Score, evaluator: &E) -> (Score, Option<Move>) {
+    let limits = SearchLimits::default();
+    let mut ctx = SearchContext::new(evaluator, &limits, None, board);
+    let score = negamax(&mut ctx, &mut board.clone(), depth, 0, alpha, beta);
+    (score, ctx.pv[0].first().cloned())
+}
+
+// Returns the score and the principal variation below this node. Once the
+// context is stopped the result is meaningless and gets thrown away. Moves
+// are made and taken back on the one board, which is left as it was found.
+// The node's best line is left in ctx.pv[ply].
+fn negamax<E: Evaluator + ?Sized>(ctx: &mut SearchContext<E>, board: &mut Board, depth: u32, ply: u32, alpha: Score, beta: Score) -> Score {
+    ctx.nodes += 1;
+    ctx.clear_pv(ply);
+
     if depth == 0 || board.is_game_over() {
         if board.is_game_over() {
-            if let Some(winner) = board.get_winner() {
-                return match winner {
-                    Color::Red => (f32::INFINITY, None),       // Red wins
-                    Color::Black => (f32::NEG_INFINITY, None), // Black wins
-                };
-            }
-            return (0.0, None); // Draw
+            return match board.get_winner() {
+                Some(winner) if winner == board.turn => f32::INFINITY,
+                Some(_) => f32::NEG_INFINITY,
+                None => 0.0, // Draw
+            };
         }
 
-        return (bar(board), None);
+        return ctx.evaluate(board);
     }
 
-    let valid_moves = board.get_valid_moves();
+    if ctx.should_stop() {
+        return 0.0;
+    }
+
+    let mut valid_moves = ctx.take_move_list(ply);
+    board.generate_moves(&mut valid_moves);
+    let result = search_moves(ctx, board, &mut valid_moves, depth, ply, alpha, beta);
+    ctx.return_move_list(ply, valid_moves);
+    result
+}
 
+// The rest of negamax, once the node's moves are generated
+fn search_moves<E: Evaluator + ?Sized>(ctx: &mut SearchContext<E>, board: &mut Board, valid_moves: &mut [Move],
+                                       depth: u32, ply: u32, alpha: Score, beta: Score) -> Score {
+    // No legal moves: the side to move is blocked and loses (or wins, in giveaway)
     if valid_moves.is_empty() {
-        return if is_maximizing_player {
-            (f32::NEG_INFINITY, None)
+        return if board.winner_when_stuck(board.turn) == board.turn { f32::INFINITY } else { f32::NEG_INFINITY };
+    }
+    if valid_moves.len() == 1 {
+        ctx.clear_pv(ply + 1);
+        ctx.update_pv(ply, &valid_moves[0]);
+        return ctx.evaluate(board);
+    }
+
+    // Transposition table: cut off on a deep enough stored bound (never at
+    // the root, which must return a move), otherwise try its move first
+    let key = board.hash();
+    if let Some(entry) = ctx.tt.as_deref().and_then(|tt| tt.probe(key)).cloned() {
+        let cutoff = ply > 0 && entry.depth >= depth && match entry.bound {
+            Bound::Exact => true,
+            Bound::Lower => entry.score >= beta,
+            Bound::Upper => entry.score <= alpha,
+        };
+        if cutoff {
+            if let Some(best_move) = &entry.best_move {
+                ctx.clear_pv(ply + 1);
+                ctx.update_pv(ply, best_move);
+            }
+            return entry.score;
+        }
+
+        if let Some(tt_move) = &entry.best_move
+            && let Some(pos) = valid_moves.iter().position(|m| m == tt_move)
+        {
+            valid_moves[..=pos].rotate_right(1);
+        }
+    }
+
+    let best_val = valid_moves.iter()
+        .try_fold((f32::NEG_INFINITY, alpha), |(best_val, alpha), mv| {
+            if beta <= alpha || ctx.stopped {
+                return Err(best_val);
+            }
+
+            let Some(undo) = ctx.make_move(board, mv) else {
+                return Ok((best_val, alpha));
+            };
+            let value = -negamax(ctx, board, depth - 1, ply + 1, -beta, -alpha);
+            ctx.unmake_move(board, mv, undo);
+
+            // Update best value, line, and alpha
+            if value > best_val {
+                ctx.update_pv(ply, mv);
+                Ok((value, alpha.max(value)))
+            } else {
+                Ok((best_val, alpha))
+            }
+        })
+        .map_or_else(
+            |early_result| early_result,
+            |(final_best, _)| final_best
+        );
+
+    if !ctx.stopped
+        && let Some(tt) = ctx.tt.as_deref_mut()
+    {
+        let bound = if best_val <= alpha {
+            Bound::Upper
+        } else if best_val >= beta {
+            Bound::Lower
         } else {
-            (f32::INFINITY, None)
+            Bound::Exact
         };
+        tt.store(Entry { key, depth, score: best_val, bound, best_move: ctx.pv[ply as usize].first().cloned() });
     }
-    if valid_moves.len() == 1 {
-        return (bar(board), valid_moves.first().cloned());
-    }
-
-    if is_maximizing_player {
-        valid_moves.into_iter()
-            .try_fold((f32::NEG_INFINITY, None, alpha), |(best_val, best_move, alpha), mv| {
-                if beta <= alpha {
-                    return Err((best_val, best_move));
-                }
-
-                let mut new_board = board.clone();
-                let _ = new_board.make_move(&mv);
-
-                let (value, _) = minimax_ab(&new_board, depth - 1, false, alpha, beta);
-
-                // Update best value, move, and alpha
-                if value > best_val {
-                    let new_alpha = alpha.max(value);
-                    Ok((value, Some(mv.clone()), new_alpha))
-                } else {
-                    Ok((best_val, best_move, alpha))
-                }
-            })
-            .map_or_else(
-                |early_result| early_result,
-                |(final_best, final_move, _)| (final_best, final_move)
-            )
-    } else {
-        valid_moves.into_iter()
-            .try_fold((f32::INFINITY, None, beta), |(best_val, best_move, beta), mv| {
-                if beta <= alpha {
-                    return Err((best_val, best_move));
-                }
-
-                let mut new_board = board.clone();
-                let _ = new_board.make_move(&mv);
-
-                let (value, _) = minimax_ab(&new_board, depth - 1, true, alpha, beta);
-
-                if value < best_val {
-                    let new_beta = beta.min(value);
-                    Ok((value, Some(mv.clone()), new_beta))
-                } else {
-                    Ok((best_val, best_move, beta))
-                }
-            })
-            .map_or_else(
-                |early_result| early_result,
-                |(final_best, final_move, _)| (final_best, final_move)
-            )
-    }
-}
\ No newline at end of file
+
+    best_val
+}
+
+// Iterative deepening alpha-beta with a transposition table kept between
+// searches (like MctsEngine keeps its trees)
+pub struct AlphaBetaEngine {
+    pub tt: TranspositionTable,
+}
+
+impl AlphaBetaEngine {
+    pub fn new(hash_mb: usize) -> Self {
+        AlphaBetaEngine { tt: TranspositionTable::new(hash_mb) }
+    }
+
+    // Forget stored results, e.g. when a new game starts
+    pub fn clear(&mut self) {
+        self.tt.clear();
+    }
+
+    // Search under the given limits. Each finished iteration replaces the
+    // result and is passed to on_iteration; an iteration cut short by a
+    // limit or the stop handle is discarded.
+    pub fn search<E, F>(&mut self, board: &Board, limits: &SearchLimits, evaluator: &E, mut on_iteration: F) -> SearchResult
+    where
+        E: Evaluator + ?Sized,
+        F: FnMut(&SearchResult),
+    {
+        let valid_moves = board.get_valid_moves();
+        let mut ctx = SearchContext::new(evaluator, limits, Some(&mut self.tt), board);
+
+        let mut result = SearchResult {
+            best_move: valid_moves.first().cloned(),
+            score: evaluator.evaluate(board),
+            depth: 0,
+            nodes: 0,
+            pv: valid_moves.first().cloned().into_iter().collect(),
+            elapsed: Duration::ZERO,
+        };
+
+        let mut root = board.clone();
+        for depth in 1..=limits.max_depth() {
+            if valid_moves.len() <= 1 {
+                break;
+            }
+
+            let score = negamax(&mut ctx, &mut root, depth, 0, f32::NEG_INFINITY, f32::INFINITY);
+            if ctx.stopped {
+                break;
+            }
+
+            result.score = score;
+            result.depth = depth;
+            if let Some(best_move) = ctx.pv[0].first() {
+                result.best_move = Some(best_move.clone());
+                result.pv.clone_from(&ctx.pv[0]);
+            }
+            result.nodes = ctx.nodes;
+            result.elapsed = ctx.start.elapsed();
+            on_iteration(&result);
+
+            // A forced win or loss won't change with more depth
+            if score.is_infinite() {
+                break;
+            }
+        }
+
+        result.nodes = ctx.nodes;
+        result.elapsed = ctx.start.elapsed();
+        result
+    }
+}
+
+// One-off iterative deepening search without a transposition table
+pub fn search<E: Evaluator + ?Sized>(board: &Board, limits: &SearchLimits, evaluator: &E) -> SearchResult {
+    AlphaBetaEngine::new(0).search(board, limits, evaluator, |_| {})
+}
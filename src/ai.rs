@@ -1,10 +1,11 @@
 use rayon::prelude::*;
 use std::cmp::Ordering;
-use crate::board::{Board, Color};
+use crate::board::Board;
+use crate::evaluator::Evaluator;
 use crate::mv::Move;
-use crate::ab_ai::{bar, ab_ai};
+use crate::ab_ai::ab_ai;
 
-pub fn parallel_loss_function(board: &Board, depth: u32) -> Option<Move> {
+pub fn parallel_loss_function<E: Evaluator + ?Sized>(board: &Board, depth: u32, evaluator: &E) -> Option<Move> {
 
     //Base Cases
     if depth == 0 || board.is_game_over() {
@@ -16,61 +17,64 @@ pub fn parallel_loss_function(board: &Board, depth: u32) -> Option<Move> {
         return None;
     }
     if depth <= 5 {
-        return ab_ai(board, depth);
+        return ab_ai(board, depth, evaluator);
     }
 
     if depth == 1 {
-        return par_eval(board, &valid_moves);
+        return par_eval(board, &valid_moves, evaluator);
     }
 
+    // Each worker gets one copy of the board and makes and takes back moves
+    // on it, rather than cloning it for every move
     valid_moves.par_iter()
-        .map(|mv| {
-            let mut new_board = board.clone();
-            let _ = new_board.make_move(mv);
+        .map_init(|| board.clone(), |new_board, mv| {
+            let undo = new_board.make_move(mv).ok()?;
 
-            let future_best_move = parallel_loss_function(&new_board, depth - 1);
+            let future_best_move = parallel_loss_function(new_board, depth - 1, evaluator);
 
+            // Scores are relative to the side to move, so they are flipped
+            // whenever it's the opponent's turn in the evaluated position
             let score = match future_best_move {
                 Some(best_future_move) => {
-                    // Apply the future best move to get the resulting position
-                    let mut future_board = new_board.clone();
-                    let _ = future_board.make_move(&best_future_move);
-
-                    bar(&future_board)
+                    // Apply the future best move to get the resulting position.
+                    // The board is reused for later moves, so the outer move
+                    // is taken back even when this one can't be played.
+                    match new_board.make_move(&best_future_move) {
+                        Ok(future_undo) => {
+                            let score = evaluator.evaluate(new_board);
+                            new_board.unmake_move(&best_future_move, future_undo);
+                            score
+                        },
+                        Err(_) => {
+                            new_board.unmake_move(mv, undo);
+                            return None;
+                        },
+                    }
                 },
                 None => {
-                    bar(&new_board)
+                    -evaluator.evaluate(new_board)
                 }
             };
-            (mv.clone(), score)
-        })
-        .max_by(|a, b| {
-            if board.turn == Color::Red {
-                a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal)
-            } else {
-                b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal)
-            }
+            new_board.unmake_move(mv, undo);
+            Some((mv.clone(), score))
         })
+        .flatten()
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))
         .map(|(mv, _)| mv)
 }
 
 /// Helper function to evaluate all moves in parallel at leaf nodes
-fn par_eval(board: &Board, valid_moves: &[Move]) -> Option<Move> {
+fn par_eval<E: Evaluator + ?Sized>(board: &Board, valid_moves: &[Move], evaluator: &E) -> Option<Move> {
     valid_moves.par_iter()
-        .map(|mv| {
-            let mut new_board = board.clone();
-            let _ = new_board.make_move(mv);
+        .map_init(|| board.clone(), |new_board, mv| {
+            let undo = new_board.make_move(mv).ok()?;
 
-            let score = bar(&new_board);
-            (mv.clone(), score)
-        })
-        .max_by(|a, b| {
-            // Maximize for Red, minimize for Black
-            if board.turn == Color::Red {
-                a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal)
-            } else {
-                b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal)
-            }
+            // The opponent is to move in new_board
+            let score = -evaluator.evaluate(new_board);
+            new_board.unmake_move(mv, undo);
+            Some((mv.clone(), score))
         })
+        .flatten()
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))
         .map(|(mv, _)| mv)
 }
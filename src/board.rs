@@ -1,16 +1,23 @@
 use crate::mv::Move;
-use crate::eval_moves::MoveEvaluator;
+use crate::rng::Rng;
+use crate::rules::Rules;
+use crate::variant::{Variant, MAX_SQUARES};
 
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(into = "BoardJson", try_from = "BoardJson"))]
 pub struct Board {
-    pub squares: [char; 32],
+    pub squares: [Piece; MAX_SQUARES], // Only the first rules().num_squares() are used
     pub turn: Color,
     pub red_pieces: u8,
     pub black_pieces: u8,
+    pub variant: Variant,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum Color {
     Red,
     Black,
@@ -18,7 +25,7 @@ pub enum Color {
 
 impl Color {
     // Functional way to toggle turn
-    fn toggle(&self) -> Self {
+    pub fn toggle(&self) -> Self {
         match self {
             Color::Red => Color::Black,
             Color::Black => Color::Red,
@@ -26,49 +33,201 @@ impl Color {
     }
 }
 
+// What stands on a square. The letters r, R, b, B (and □ for an empty
+// square) are only for display; see to_char and from_char.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Piece {
+    #[default]
+    Empty,
+    RedMan,
+    RedKing,
+    BlackMan,
+    BlackKing,
+}
+
+impl Piece {
+    pub fn man(color: Color) -> Self {
+        match color {
+            Color::Red => Piece::RedMan,
+            Color::Black => Piece::BlackMan,
+        }
+    }
+
+    pub fn king(color: Color) -> Self {
+        match color {
+            Color::Red => Piece::RedKing,
+            Color::Black => Piece::BlackKing,
+        }
+    }
+
+    // Owner of the piece, None for an empty square
+    pub fn color(self) -> Option<Color> {
+        match self {
+            Piece::RedMan | Piece::RedKing => Some(Color::Red),
+            Piece::BlackMan | Piece::BlackKing => Some(Color::Black),
+            Piece::Empty => None,
+        }
+    }
+
+    pub fn is_king(self) -> bool {
+        matches!(self, Piece::RedKing | Piece::BlackKing)
+    }
+
+    pub fn is_empty(self) -> bool {
+        self == Piece::Empty
+    }
+
+    // The piece after promotion; kings and empty squares stay as they are
+    pub fn crowned(self) -> Self {
+        match self.color() {
+            Some(color) => Piece::king(color),
+            None => Piece::Empty,
+        }
+    }
+
+    pub fn to_char(self) -> char {
+        match self {
+            Piece::Empty => '□',
+            Piece::RedMan => 'r',
+            Piece::RedKing => 'R',
+            Piece::BlackMan => 'b',
+            Piece::BlackKing => 'B',
+        }
+    }
+
+    // Read a display letter back; anything else is an empty square
+    pub fn from_char(c: char) -> Self {
+        match c {
+            'r' => Piece::RedMan,
+            'R' => Piece::RedKing,
+            'b' => Piece::BlackMan,
+            'B' => Piece::BlackKing,
+            _ => Piece::Empty,
+        }
+    }
+}
+
+// Boards serialize as their variant and FEN, {"variant": "american", "fen":
+// "W:W21,22,...:B1,2,..."}, so the JSON doesn't change with how squares are
+// stored. Ranges are read as well, and a missing variant is American.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct BoardJson {
+    #[serde(default)]
+    variant: Variant,
+    fen: String,
+}
+
+#[cfg(feature = "serde")]
+impl From<Board> for BoardJson {
+    fn from(board: Board) -> Self {
+        BoardJson { variant: board.variant, fen: board.to_fen() }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<BoardJson> for Board {
+    type Error = String;
+
+    fn try_from(json: BoardJson) -> Result<Self, String> {
+        Board::from_variant_fen(json.variant, &json.fen)
+    }
+}
+
+// What make_move can't work out again from the move alone: the piece as it
+// stood before a possible promotion, and which of m.captures were kings (bit
+// i for m.captures[i])
+#[derive(Debug, Clone, Copy)]
+pub struct Undo {
+    piece: Piece,
+    captured_kings: u64,
+}
+
+impl Undo {
+    // The piece that moved, as it stood before a possible promotion
+    pub fn moved(&self) -> Piece {
+        self.piece
+    }
+
+    // The piece m.captures[i] took off the board
+    pub fn captured(&self, i: usize) -> Piece {
+        let Some(opponent) = self.piece.color().map(|color| color.toggle()) else {
+            return Piece::Empty;
+        };
+        if self.captured_kings & (1 << i) != 0 { Piece::king(opponent) } else { Piece::man(opponent) }
+    }
+}
+
+// Zobrist keys: one per (piece kind, square) plus one for Black to move,
+// drawn at compile time from a fixed-seed Rng so hashes are stable across runs
+const fn zobrist_keys() -> [u64; 4 * MAX_SQUARES + 1] {
+    let mut keys = [0u64; 4 * MAX_SQUARES + 1];
+    let mut rng = Rng::new(0x2545_F491_4F6C_DD1D);
+    let mut i = 0;
+    while i < keys.len() {
+        keys[i] = rng.next_u64();
+        i += 1;
+    }
+    keys
+}
+
+const ZOBRIST: [u64; 4 * MAX_SQUARES + 1] = zobrist_keys();
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Board {
     // Create a new board with the initial setup
     pub fn new() -> Self {
-        // Use iterators and collect to create the initial squares array
-        let squares = (0..32)
-            .map(|i| match i {
-                0..=11 => 'b',        // black pieces
-                20..=31 => 'r',       // red pieces
-                _ => '□',             // empty squares
-            })
-            .collect::<Vec<char>>()
-            .try_into()
-            .unwrap();  // Safe because we know it's exactly 32 elements
-
-        Board {
-            squares,
+        Self::for_variant(Variant::American)
+    }
+
+    // Start position of a variant
+    pub fn for_variant(variant: Variant) -> Self {
+        let mut board = Board {
+            squares: [Piece::Empty; MAX_SQUARES],
             turn: Color::Red, // Red goes first in American checkers
-            red_pieces: 12,
-            black_pieces: 12,
-        }
+            red_pieces: 0,
+            black_pieces: 0,
+            variant,
+        };
+        variant.rules().setup(&mut board.squares);
+        board.count_pieces();
+        board
     }
 
-    pub fn index_to_coords(&self, index: usize) -> (usize, usize) {
-        let row_group = index / 8;
-        let pos_in_group = index % 8;
+    pub fn rules(&self) -> &'static dyn Rules {
+        self.variant.rules()
+    }
 
-        let row = if pos_in_group < 4 {
-            row_group * 2
-        } else {
-            row_group * 2 + 1
-        };
+    pub fn num_squares(&self) -> usize {
+        self.rules().num_squares()
+    }
 
-        let col = if row % 2 == 0 {
-            (pos_in_group % 4) * 2 + 1
+    fn count_pieces(&mut self) {
+        let count = |color| self.squares.iter().filter(|p| p.color() == Some(color)).count() as u8;
+        (self.red_pieces, self.black_pieces) = (count(Color::Red), count(Color::Black));
+    }
+
+    // Each row holds size/2 dark squares, numbered left to right from the top
+    pub fn index_to_coords(&self, index: usize) -> (usize, usize) {
+        let per_row = self.rules().size() / 2;
+        let row = index / per_row;
+
+        // Dark squares start in the second column on the top row, or in the
+        // first on a mirrored board
+        let col = if row.is_multiple_of(2) != self.rules().mirrored() {
+            (index % per_row) * 2 + 1
         } else {
-            (pos_in_group % 4) * 2
+            (index % per_row) * 2
         };
 
         (row, col)
     }
 
-    // In board.rs, replace the existing coords_to_index with this implementation
-
     pub fn coords_to_index<T: TryInto<usize>>(&self, row: T, col: T) -> Option<usize>
     where
         <T as TryInto<usize>>::Error: std::fmt::Debug
@@ -85,50 +244,137 @@ impl Board {
             Err(_) => return None,
         };
 
+        let size = self.rules().size();
+
         // Use pattern matching for early returns
         match (r, c) {
-            (r, c) if r >= 8 || c >= 8 => None,
-            (r, c) if (r + c) % 2 == 0 => None, // Light squares are not used
-            _ => {
-                let index = if r % 2 == 0 {
-                    // Even row (0, 2, 4, 6) - valid squares are at odd columns
-                    (r / 2) * 8 + (c / 2)
-                } else {
-                    // Odd row (1, 3, 5, 7) - valid squares are at even columns
-                    (r / 2) * 8 + 4 + (c / 2)
-                };
-
-                if index < 32 { Some(index) } else { None }
-            }
+            (r, c) if r >= size || c >= size => None,
+            (r, c) if (r + c + self.rules().mirrored() as usize).is_multiple_of(2) => None, // Light squares are not used
+            // Each row holds size/2 dark squares, one in every two columns
+            _ => Some(r * (size / 2) + c / 2),
         }
     }
 
+    // Print the default diagram; see render::Renderer for other styles
     pub fn display(&self) {
-        println!("\tA\tB\tC\tD\tE\tF\tG\tH");
-        println!("\t_____\t_____\t_____\t_____\t_____\t_____\t_____\t_____");
-
-        // Use iterators for display rows
-        (0..8).for_each(|row| {
-            print!("{}|\t", row + 1);
-
-            // Use iterators for columns within row
-            (0..8).for_each(|col| {
-                let piece = if (row + col) % 2 == 1 {
-                    self.coords_to_index(row, col)
-                        .map(|idx| self.squares[idx])
-                        .unwrap_or(' ')
-                } else {
-                    '■'
+        print!("{}", self);
+    }
+
+    // PDN style FEN, e.g. "W:W21,22,K30:B1,2,K5". Squares use the standard 1-32
+    // (or 1-50) numbering, index + 1. Red plays the "W" side because it sits on
+    // the high numbers.
+    pub fn to_fen(&self) -> String {
+        let side = |color: Color| {
+            (0..self.num_squares())
+                .filter_map(|i| match self.squares[i] {
+                    p if p.color() != Some(color) => None,
+                    p if p.is_king() => Some(format!("K{}", i + 1)),
+                    _ => Some(format!("{}", i + 1)),
+                })
+                .collect::<Vec<String>>()
+                .join(",")
+        };
+
+        let turn = match self.turn {
+            Color::Red => 'W',
+            Color::Black => 'B',
+        };
+
+        format!("{}:W{}:B{}", turn, side(Color::Red), side(Color::Black))
+    }
+
+    // Parse a FEN produced by to_fen. Also accepts the [FEN "..."] tag form,
+    // a trailing '.', and square ranges like "1-12".
+    pub fn from_fen(fen: &str) -> Result<Self, String> {
+        Self::from_variant_fen(Variant::American, fen)
+    }
+
+    // FEN does not say which game it is, so other variants name it
+    pub fn from_variant_fen(variant: Variant, fen: &str) -> Result<Self, String> {
+        let fen = fen.trim()
+            .trim_start_matches("[FEN")
+            .trim_end_matches(']')
+            .trim()
+            .trim_matches('"')
+            .trim_end_matches('.');
+
+        let mut fields = fen.split(':');
+
+        let turn = match fields.next().map(str::trim) {
+            Some("W") => Color::Red,
+            Some("B") => Color::Black,
+            other => return Err(format!("invalid side to move: {:?}", other)),
+        };
+
+        let mut board = Board {
+            squares: [Piece::Empty; MAX_SQUARES],
+            turn,
+            red_pieces: 0,
+            black_pieces: 0,
+            variant,
+        };
+
+        for field in fields {
+            let field = field.trim();
+            let color = match field.chars().next() {
+                Some('W') => Color::Red,
+                Some('B') => Color::Black,
+                _ => return Err(format!("invalid piece list: '{}'", field)),
+            };
+
+            for item in field[1..].split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                let (piece, squares) = match item.strip_prefix('K') {
+                    Some(rest) => (Piece::king(color), rest),
+                    None => (Piece::man(color), item),
+                };
+
+                let parse = |s: &str| -> Result<usize, String> {
+                    match s.trim().parse::<usize>() {
+                        Ok(n) if (1..=variant.rules().num_squares()).contains(&n) => Ok(n - 1),
+                        _ => Err(format!("invalid square: '{}'", s)),
+                    }
+                };
+
+                let (first, last) = match squares.split_once('-') {
+                    Some((a, b)) => (parse(a)?, parse(b)?),
+                    None => (parse(squares)?, parse(squares)?),
                 };
 
-                print!("|{}|\t", piece);
-            });
+                for index in first..=last {
+                    board.squares[index] = piece;
+                }
+            }
+        }
+
+        board.count_pieces();
+
+        // No game reaches more pieces than a side starts with, and captures
+        // of at most that many are what a Move has room for
+        let most = variant.rules().rows_of_men() * variant.rules().size() / 2;
+        if board.red_pieces as usize > most || board.black_pieces as usize > most {
+            return Err(format!("a side can have at most {} pieces", most));
+        }
 
-            println!();
+        Ok(board)
+    }
+
+    // Zobrist hash of the position (pieces and side to move)
+    pub fn hash(&self) -> u64 {
+        let pieces = (0..self.num_squares()).fold(0u64, |hash, i| {
+            let kind = match self.squares[i] {
+                Piece::RedMan => 0,
+                Piece::RedKing => 1,
+                Piece::BlackMan => 2,
+                Piece::BlackKing => 3,
+                Piece::Empty => return hash,
+            };
+            hash ^ ZOBRIST[kind * MAX_SQUARES + i]
         });
 
-        println!("Turn: {:?}", self.turn);
-        println!("Red pieces: {}, Black pieces: {}", self.red_pieces, self.black_pieces);
+        match self.turn {
+            Color::Red => pieces,
+            Color::Black => pieces ^ ZOBRIST[4 * MAX_SQUARES],
+        }
     }
 
     // Check if the game is over - for now, only when all pieces are captured
@@ -138,89 +384,156 @@ impl Board {
 
     pub fn get_winner(&self) -> Option<Color> {
         match (self.red_pieces, self.black_pieces) {
-            (0, _) => Some(Color::Black),
-            (_, 0) => Some(Color::Red),
+            (0, _) => Some(self.winner_when_stuck(Color::Red)),
+            (_, 0) => Some(self.winner_when_stuck(Color::Black)),
             _ => None,
         }
     }
 
-    // Get all valid moves for the current player using the parallel implementation
-    pub fn get_valid_moves(&self) -> Vec<Move> {
-        let evaluator = MoveEvaluator::new(self.clone());
-        evaluator.par_possible_moves()
+    // Who wins once `stuck` can't move, having no pieces left or being
+    // blocked: normally the other side, in giveaway `stuck` itself
+    pub fn winner_when_stuck(&self, stuck: Color) -> Color {
+        self.rules().winner_when_stuck(stuck)
     }
 
-    // Execute a move on the board
-    pub fn make_move(&mut self, m: &Move) -> Result<(), &'static str> {
-        // Get the piece from the source position
-        let piece = self.squares[m.from];
+    // The winner of a finished game, None while the side to move has a move
+    pub fn winner(&self) -> Option<Color> {
+        self.get_winner()
+            .or_else(|| self.get_valid_moves().is_empty().then(|| self.winner_when_stuck(self.turn)))
+    }
 
-        // Create a new board with the piece moved
-        if m.path.len() > 2 {
-            // For multi-jumps
-
-            // Remove piece from starting position
-            self.squares[m.from] = '□';
-
-            // Process each jump segment using iterators
-            m.path.iter().enumerate().skip(1).for_each(|(i, &to_pos)| {
-                // Place piece at destination
-                if i == m.path.len() - 1 {
-                    // Final destination
-                    self.squares[to_pos] = piece;
-                } else {
-                    // Intermediate position - place and then clear for next jump
-                    self.squares[to_pos] = piece;
-                    self.squares[to_pos] = '□';
-                }
+    // Get all valid moves for the current player under the board's rules
+    pub fn get_valid_moves(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
+        self.generate_moves(&mut moves);
+        moves
+    }
 
-                // Handle captures
-                if i - 1 < m.captures.len() {
-                    let capture_index = m.captures[i - 1];
-                    let captured_piece = self.squares[capture_index];
-                    self.squares[capture_index] = '□';
+    // The same moves appended to a list the caller keeps, so a search can
+    // reuse one list per ply instead of allocating at every node
+    pub fn generate_moves(&self, moves: &mut Vec<Move>) {
+        self.rules().generate_moves(self, moves)
+    }
 
-                    // Update piece count
-                    self.update_piece_count(captured_piece);
-                }
-            });
-        } else {
-            // Simple move or single capture
-            self.squares[m.from] = '□';
-            self.squares[m.to] = piece;
+    // Execute a move on the board, returning what unmake_move needs to take
+    // it back. The move isn't checked against the rules (the generator and
+    // Move::parse do that), but one that doesn't fit the board is refused
+    // before anything changes, so the piece counts can't drift.
+    pub fn make_move(&mut self, m: &Move) -> Result<Undo, &'static str> {
+        let piece = self.squares[m.from];
+        if piece.color() != Some(self.turn) {
+            return Err("no piece of the side to move on the origin square");
+        }
+        if m.to != m.from && !self.squares[m.to].is_empty() {
+            return Err("destination square is occupied");
+        }
+        let mut captured = 0u64;
+        for capture_index in m.captures.iter() {
+            if self.squares[capture_index].color() != Some(self.turn.toggle()) || captured & (1 << capture_index) != 0 {
+                return Err("captured square doesn't hold an opponent piece");
+            }
+            captured |= 1 << capture_index;
+        }
 
-            // Process captures using iterators
-            m.captures.iter().for_each(|&capture_index| {
-                let captured_piece = self.squares[capture_index];
-                self.squares[capture_index] = '□';
+        let mut undo = Undo { piece, captured_kings: 0 };
 
-                // Update piece count
-                self.update_piece_count(captured_piece);
-            });
+        // The origin is cleared first, as a capture can end where it began
+        self.squares[m.from] = Piece::Empty;
+        for (i, capture_index) in m.captures.iter().enumerate() {
+            let captured_piece = self.squares[capture_index];
+            if captured_piece.is_king() {
+                undo.captured_kings |= 1 << i;
+            }
+            self.squares[capture_index] = Piece::Empty;
+            self.update_piece_count(captured_piece);
         }
+        self.squares[m.to] = piece;
 
         // Check if the piece should be promoted to a king
-        if crate::mv::promote(self, m.to) {
-            // Functional approach with pattern matching
-            self.squares[m.to] = match self.squares[m.to] {
-                'r' => 'R',
-                'b' => 'B',
-                other => other,
-            };
+        if crate::mv::promote(self, m) {
+            self.squares[m.to] = self.squares[m.to].crowned();
         }
 
         // End Turn - use the toggle method
         self.turn = self.turn.toggle();
 
-        Ok(())
+        Ok(undo)
+    }
+
+    // Take back m, which must be the last move made, restoring the captured
+    // pieces, an uncrowned man and the side to move
+    pub fn unmake_move(&mut self, m: &Move, undo: Undo) {
+        self.turn = self.turn.toggle();
+
+        let opponent = self.turn.toggle();
+        self.squares[m.to] = Piece::Empty;
+        for (i, capture_index) in m.captures.iter().enumerate() {
+            self.squares[capture_index] = undo.captured(i);
+        }
+        match opponent {
+            Color::Red => self.red_pieces += m.captures.len() as u8,
+            Color::Black => self.black_pieces += m.captures.len() as u8,
+        }
+        self.squares[m.from] = undo.piece;
     }
 
     // Helper function to update piece count
-    fn update_piece_count(&mut self, captured_piece: char) {
-        match captured_piece {
-            'r' | 'R' => self.red_pieces -= 1,
-            'b' | 'B' => self.black_pieces -= 1,
-            _ => {},
+    fn update_piece_count(&mut self, captured_piece: Piece) {
+        match captured_piece.color() {
+            Some(Color::Red) => self.red_pieces -= 1,
+            Some(Color::Black) => self.black_pieces -= 1,
+            None => {},
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mv::SquareList;
+    use crate::perft;
+
+    // Make and take back every move down to `depth` plies, checking that
+    // each takeback restores the position exactly
+    fn assert_round_trips(board: &mut Board, depth: u32) {
+        if depth == 0 {
+            return;
+        }
+
+        let (squares, turn, red, black, hash) =
+            (board.squares, board.turn, board.red_pieces, board.black_pieces, board.hash());
+
+        for m in board.get_valid_moves() {
+            let undo = board.make_move(&m).expect("generated moves can be made");
+            assert_round_trips(board, depth - 1);
+            board.unmake_move(&m, undo);
+
+            assert_eq!(board.squares, squares, "squares after taking back {:?}", m);
+            assert_eq!(board.turn, turn, "side to move after taking back {:?}", m);
+            assert_eq!((board.red_pieces, board.black_pieces), (red, black), "piece counts after taking back {:?}", m);
+            assert_eq!(board.hash(), hash, "hash after taking back {:?}", m);
+        }
+    }
+
+    #[test]
+    fn unmake_restores_every_suite_position() {
+        for variant in Variant::ALL {
+            for case in perft::suite(variant) {
+                let mut board = Board::from_variant_fen(variant, case.fen).unwrap();
+                assert_round_trips(&mut board, 3);
+            }
+        }
+    }
+
+    #[test]
+    fn make_move_refuses_moves_that_dont_fit_the_board() {
+        let mut board = Board::new();
+        let fen = board.to_fen();
+
+        // An empty origin, an occupied destination and a capture of nothing
+        let jump = |list: &[usize]| SquareList::from_slice(list).unwrap();
+        for m in [Move::new(13, 17), Move::new(21, 25), Move::with_path(21, 14, jump(&[17]), jump(&[21, 14]))] {
+            assert!(board.make_move(&m).is_err(), "{:?} was accepted", m);
+            assert_eq!(board.to_fen(), fen);
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+// A small command line parser: every subcommand declares its flags, and
+// anything else on the command line is a usage error.
+
+#[derive(Debug, Clone, Copy)]
+pub struct Flag {
+    pub name: &'static str,          // Without the leading "--"
+    pub value: Option<&'static str>, // Placeholder shown in help; None for switches
+    pub help: &'static str,
+}
+
+pub struct Command {
+    pub name: &'static str,
+    pub args: &'static str,          // Positional arguments, for the usage line
+    pub about: &'static str,
+    pub flags: &'static [Flag],
+}
+
+// Why a command failed, which decides the exit code
+#[derive(Debug)]
+pub enum CliError {
+    Usage(String),   // Bad command line, exit code 2
+    Failed(String),  // The command ran and failed, exit code 1
+}
+
+impl CliError {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            CliError::Usage(_) => 2,
+            CliError::Failed(_) => 1,
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::Usage(message) | CliError::Failed(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<String> for CliError {
+    fn from(message: String) -> Self {
+        CliError::Failed(message)
+    }
+}
+
+impl From<&str> for CliError {
+    fn from(message: &str) -> Self {
+        CliError::Failed(message.to_string())
+    }
+}
+
+pub struct Matches {
+    pub positional: Vec<String>,
+    values: HashMap<&'static str, String>,
+    switches: Vec<&'static str>,
+    pub help: bool,
+}
+
+impl Matches {
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    pub fn is_set(&self, name: &str) -> bool {
+        self.switches.contains(&name)
+    }
+
+    pub fn parse<T: FromStr>(&self, name: &str) -> Result<Option<T>, CliError> {
+        self.value(name)
+            .map(|value| value.parse().map_err(|_| CliError::Usage(format!("invalid value for --{}: {}", name, value))))
+            .transpose()
+    }
+
+    pub fn parse_or<T: FromStr>(&self, name: &str, default: T) -> Result<T, CliError> {
+        Ok(self.parse(name)?.unwrap_or(default))
+    }
+}
+
+impl Command {
+    // Accepts "--name value", "--name=value" and "-h"/"--help"
+    pub fn parse(&self, args: &[String]) -> Result<Matches, CliError> {
+        let mut matches = Matches {
+            positional: Vec::new(),
+            values: HashMap::new(),
+            switches: Vec::new(),
+            help: false,
+        };
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            if arg == "-h" || arg == "--help" {
+                matches.help = true;
+                continue;
+            }
+
+            let Some(long) = arg.strip_prefix("--") else {
+                matches.positional.push(arg.clone());
+                continue;
+            };
+
+            let (name, inline) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (long, None),
+            };
+            let flag = self.flags.iter()
+                .find(|f| f.name == name)
+                .ok_or_else(|| CliError::Usage(format!("unknown option --{} for '{}'", name, self.name)))?;
+
+            match (flag.value, inline) {
+                (Some(_), Some(value)) => { matches.values.insert(flag.name, value); },
+                (Some(_), None) => {
+                    let value = args.next()
+                        .ok_or_else(|| CliError::Usage(format!("--{} needs a value", name)))?;
+                    matches.values.insert(flag.name, value.clone());
+                },
+                (None, Some(_)) => return Err(CliError::Usage(format!("--{} doesn't take a value", name))),
+                (None, None) => matches.switches.push(flag.name),
+            }
+        }
+
+        Ok(matches)
+    }
+
+    pub fn usage(&self) -> String {
+        let mut text = format!("Usage: checkersBot {}", self.name);
+        if !self.flags.is_empty() {
+            text.push_str(" [options]");
+        }
+        if !self.args.is_empty() {
+            text.push(' ');
+            text.push_str(self.args);
+        }
+        text
+    }
+
+    pub fn help(&self) -> String {
+        let mut text = format!("{}\n\n{}\n", self.about, self.usage());
+        if !self.flags.is_empty() {
+            text.push_str("\nOptions:\n");
+            for flag in self.flags {
+                let left = match flag.value {
+                    Some(value) => format!("--{} {}", flag.name, value),
+                    None => format!("--{}", flag.name),
+                };
+                text.push_str(&format!("  {:<24} {}\n", left, flag.help));
+            }
+        }
+        text
+    }
+}
+
+pub fn help(commands: &[Command]) -> String {
+    let mut text = String::from("American Checkers engine\n\nUsage: checkersBot <command> [options]\n\nCommands:\n");
+    for command in commands {
+        text.push_str(&format!("  {:<10} {}\n", command.name, command.about));
+    }
+    text.push_str("\nRun 'checkersBot <command> --help' for the options of a command.\n");
+    text.push_str("Exit codes: 0 success, 1 the command failed, 2 invalid command line.\n");
+    text
+}
@@ -0,0 +1,339 @@
+use rayon::prelude::*;
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use crate::ab_ai::search;
+use crate::board::{Board, Color, Piece};
+use crate::evaluator::Evaluator;
+use crate::rng::Rng;
+use crate::search::SearchLimits;
+
+// Self-play training data: every quiet position reached in a game, with the
+// search score and the final result of that game
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DataFormat {
+    Csv,    // "fen;score;result" lines, readable by tune::load_positions
+    Binary, // Fixed 16 byte records, see encode_record
+}
+
+impl DataFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "csv" => Some(DataFormat::Csv),
+            "bin" | "binary" => Some(DataFormat::Binary),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DatagenConfig {
+    pub games: u32,
+    pub nodes: u64,          // Search budget per move
+    pub random_plies: u32,   // Random moves played at the start of each game
+    pub max_plies: u32,      // Games this long are scored as draws
+    pub seed: u64,
+    pub output: String,
+    pub format: DataFormat,
+    pub resume: bool,        // Append to an earlier run instead of starting over
+}
+
+impl Default for DatagenConfig {
+    fn default() -> Self {
+        DatagenConfig {
+            games: 100,
+            nodes: 5000,
+            random_plies: 6,
+            max_plies: 200,
+            seed: 1,
+            output: "datagen.csv".to_string(),
+            format: DataFormat::Csv,
+            resume: false,
+        }
+    }
+}
+
+// Scores and results are from Red's point of view, like the eval bar
+#[derive(Debug, Clone)]
+pub struct Record {
+    pub board: Board,
+    pub score: f32,
+    pub result: f32,  // 1.0 Red win, 0.5 draw, 0.0 Black win
+}
+
+fn result_notation(result: f32) -> &'static str {
+    match result {
+        r if r > 0.75 => "1-0",
+        r if r < 0.25 => "0-1",
+        _ => "1/2-1/2",
+    }
+}
+
+// Binary record: red, black and king bitboards (u32 each, bit i = square
+// index i), side to move (u8, 0 = Red), score in hundredths (i16), result
+// (u8: 0 Black win, 1 draw, 2 Red win). 16 bytes, little endian.
+const RECORD_SIZE: usize = 16;
+
+fn encode_record(record: &Record) -> [u8; RECORD_SIZE] {
+    let (mut red, mut black, mut kings) = (0u32, 0u32, 0u32);
+    for i in 0..32 {
+        let piece = record.board.squares[i];
+        match piece.color() {
+            Some(Color::Red) => red |= 1 << i,
+            Some(Color::Black) => black |= 1 << i,
+            None => continue,
+        }
+        if piece.is_king() {
+            kings |= 1 << i;
+        }
+    }
+
+    let turn: u8 = match record.board.turn {
+        Color::Red => 0,
+        Color::Black => 1,
+    };
+    let score = (record.score * 100.0).round().clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+    let result = (record.result * 2.0).round() as u8;
+
+    let mut bytes = [0u8; RECORD_SIZE];
+    bytes[0..4].copy_from_slice(&red.to_le_bytes());
+    bytes[4..8].copy_from_slice(&black.to_le_bytes());
+    bytes[8..12].copy_from_slice(&kings.to_le_bytes());
+    bytes[12] = turn;
+    bytes[13..15].copy_from_slice(&score.to_le_bytes());
+    bytes[15] = result;
+    bytes
+}
+
+fn decode_record(bytes: &[u8]) -> Record {
+    let word = |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+    let (red, black, kings) = (word(0), word(4), word(8));
+
+    let mut board = Board::new();
+    for i in 0..32 {
+        let king = kings & (1 << i) != 0;
+        board.squares[i] = match (red & (1 << i) != 0, black & (1 << i) != 0, king) {
+            (true, _, false) => Piece::RedMan,
+            (true, _, true) => Piece::RedKing,
+            (_, true, false) => Piece::BlackMan,
+            (_, true, true) => Piece::BlackKing,
+            _ => Piece::Empty,
+        };
+    }
+    board.red_pieces = red.count_ones() as u8;
+    board.black_pieces = black.count_ones() as u8;
+    board.turn = if bytes[12] == 0 { Color::Red } else { Color::Black };
+
+    Record {
+        board,
+        score: i16::from_le_bytes([bytes[13], bytes[14]]) as f32 / 100.0,
+        result: bytes[15] as f32 / 2.0,
+    }
+}
+
+fn format_record(record: &Record, format: DataFormat) -> Vec<u8> {
+    match format {
+        DataFormat::Csv => format!(
+            "{};{:.2};{}\n",
+            record.board.to_fen(),
+            record.score,
+            result_notation(record.result)
+        ).into_bytes(),
+        DataFormat::Binary => encode_record(record).to_vec(),
+    }
+}
+
+// Read back a file written by run()
+pub fn read_records(path: &str, format: DataFormat) -> Result<Vec<Record>, String> {
+    match format {
+        DataFormat::Csv => {
+            let text = fs::read_to_string(path)
+                .map_err(|e| format!("could not read '{}': {}", path, e))?;
+
+            text.lines()
+                .filter(|line| !line.trim().is_empty())
+                .enumerate()
+                .map(|(line_no, line)| {
+                    let fields: Vec<&str> = line.split(';').collect();
+                    let invalid = || format!("line {}: expected 'fen;score;result'", line_no + 1);
+                    if fields.len() != 3 {
+                        return Err(invalid());
+                    }
+
+                    let board = Board::from_fen(fields[0])
+                        .map_err(|e| format!("line {}: {}", line_no + 1, e))?;
+                    let score = fields[1].parse().map_err(|_| invalid())?;
+                    let result = match fields[2] {
+                        "1-0" => 1.0,
+                        "0-1" => 0.0,
+                        "1/2-1/2" => 0.5,
+                        _ => return Err(invalid()),
+                    };
+
+                    Ok(Record { board, score, result })
+                })
+                .collect()
+        },
+        DataFormat::Binary => {
+            let bytes = fs::read(path)
+                .map_err(|e| format!("could not read '{}': {}", path, e))?;
+            if bytes.len() % RECORD_SIZE != 0 {
+                return Err(format!("'{}' is not a whole number of records", path));
+            }
+            Ok(bytes.chunks_exact(RECORD_SIZE).map(decode_record).collect())
+        },
+    }
+}
+
+// Play one self-play game and return its quiet positions. Each game gets its
+// own generator derived from the seed, so a resumed run plays the same games
+// it would have played without the interruption.
+pub fn play_game<E: Evaluator + ?Sized>(config: &DatagenConfig, game: u32, evaluator: &E) -> Vec<Record> {
+    let mut rng = Rng::new(config.seed ^ (game as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    let limits = SearchLimits::nodes(config.nodes);
+    let mut board = Board::new();
+    let mut positions: Vec<(Board, f32)> = Vec::new();
+    let mut result = 0.5;
+
+    for ply in 0..config.max_plies {
+        let valid_moves = board.get_valid_moves();
+
+        if board.is_game_over() || valid_moves.is_empty() {
+            // The side to move is out of pieces or blocked; the rules say who won
+            result = match board.get_winner().unwrap_or(board.winner_when_stuck(board.turn)) {
+                Color::Red => 1.0,
+                Color::Black => 0.0,
+            };
+            break;
+        }
+
+        // Randomized opening
+        if ply < config.random_plies {
+            let mv = &valid_moves[rng.below(valid_moves.len())];
+            let _ = board.make_move(mv);
+            continue;
+        }
+
+        let found = search(&board, &limits, evaluator);
+
+        // Only record quiet positions: the generator returns captures alone
+        // whenever one is available
+        let quiet = valid_moves[0].captures.is_empty();
+        if quiet && found.score.is_finite() {
+            let red_score = match board.turn {
+                Color::Red => found.score,
+                Color::Black => -found.score,
+            };
+            positions.push((board.clone(), red_score));
+        }
+
+        match found.best_move {
+            Some(mv) => { let _ = board.make_move(&mv); },
+            None => break,
+        }
+    }
+
+    positions.into_iter()
+        .map(|(board, score)| Record { board, score, result })
+        .collect()
+}
+
+fn progress_path(config: &DatagenConfig) -> String {
+    format!("{}.progress", config.output)
+}
+
+// Games already played by an earlier run with the same seed
+fn read_progress(config: &DatagenConfig) -> Result<u32, String> {
+    let path = progress_path(config);
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(_) => return Ok(0),
+    };
+
+    let value = |key: &str| {
+        text.lines()
+            .filter_map(|line| line.split_once('='))
+            .find(|(k, _)| k.trim() == key)
+            .and_then(|(_, v)| v.trim().parse::<u64>().ok())
+    };
+
+    match (value("games"), value("seed")) {
+        (Some(games), Some(seed)) if seed == config.seed => Ok(games as u32),
+        (Some(_), Some(seed)) => Err(format!("'{}' was written with seed {}, not {}", path, seed, config.seed)),
+        _ => Err(format!("'{}' is not a valid progress file", path)),
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DatagenStats {
+    pub resumed: u32,       // Games an earlier run had already played
+    pub known: usize,       // Positions already in the output file
+    pub games: u32,         // Games played by this run
+    pub written: usize,
+    pub duplicates: usize,
+}
+
+// Generate data until config.games games have been played in total. Games are
+// played in parallel batches; after each batch the new unique positions are
+// appended and the progress file updated, so a long run can be stopped and
+// resumed with `resume`. The stats are passed to on_progress once before the
+// first batch (with games at 0) and after every batch.
+pub fn run<E, F>(config: &DatagenConfig, evaluator: &E, mut on_progress: F) -> Result<DatagenStats, String>
+where
+    E: Evaluator + ?Sized,
+    F: FnMut(&DatagenStats),
+{
+    let mut seen: HashSet<u64> = HashSet::new();
+
+    let first_game = if config.resume && fs::metadata(&config.output).is_ok() {
+        read_records(&config.output, config.format)?
+            .iter()
+            .for_each(|record| { seen.insert(record.board.hash()); });
+        read_progress(config)?
+    } else {
+        File::create(&config.output)
+            .map_err(|e| format!("could not create '{}': {}", config.output, e))?;
+        0
+    };
+
+    let mut stats = DatagenStats { resumed: first_game, known: seen.len(), ..DatagenStats::default() };
+    on_progress(&stats);
+
+    let file = OpenOptions::new()
+        .append(true)
+        .open(&config.output)
+        .map_err(|e| format!("could not open '{}': {}", config.output, e))?;
+    let mut out = BufWriter::new(file);
+
+    let batch_size = rayon::current_num_threads().max(1) as u32;
+    let mut game = first_game;
+
+    while game < config.games {
+        let batch_end = (game + batch_size).min(config.games);
+
+        let batch: Vec<Vec<Record>> = (game..batch_end)
+            .into_par_iter()
+            .map(|g| play_game(config, g, evaluator))
+            .collect();
+
+        for record in batch.into_iter().flatten() {
+            if seen.insert(record.board.hash()) {
+                out.write_all(&format_record(&record, config.format))
+                    .map_err(|e| format!("write failed: {}", e))?;
+                stats.written += 1;
+            } else {
+                stats.duplicates += 1;
+            }
+        }
+
+        out.flush().map_err(|e| format!("write failed: {}", e))?;
+        fs::write(progress_path(config), format!("games = {}\nseed = {}\n", batch_end, config.seed))
+            .map_err(|e| format!("could not write progress: {}", e))?;
+
+        stats.games += batch_end - game;
+        game = batch_end;
+        on_progress(&stats);
+    }
+
+    Ok(stats)
+}
@@ -0,0 +1,550 @@
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+use crate::board::{Board, Color};
+use crate::mv::Move;
+use crate::variant::Variant;
+
+// DamExchange Protocol (DXP) over TCP. Messages are ASCII, fixed width and
+// terminated by a NUL byte; the first character gives the type. DXP was
+// designed for international draughts, squares 1-50; other variants use the
+// same messages with their own square numbers (1-32 on 8x8 boards), and a
+// specified start position lists every square. The messages don't name the
+// game, so both sides have to be started with the same variant. White is Red
+// (the side on the high-numbered squares) and Zwart is Black.
+//
+//   GAMEREQ  R vv name[32] follower-color[1] minutes[3] moves[3] A|B [color[1] squares[N]]
+//   GAMEACC  A name[32] code[1]            0 accepted, 1 color, 2 time, 3 moves, 9 refused
+//   MOVE     M time[4] from[2] to[2] n[2] captured[2]*n
+//   GAMEEND  E reason[1] stop[1]           reason 0 unknown, 1 I lose, 2 draw, 3 I win
+//   CHAT     C text
+//   BACKREQ  B move-number[3] color[1]
+//   BACKACC  K code[1]                     0 accepted, 1 not supported, 2 refused
+
+pub const DEFAULT_PORT: u16 = 27531;
+const VERSION: &str = "01";
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    GameReq {
+        name: String,
+        follower_color: Color,
+        minutes: u32,
+        moves: u32,
+        position: Option<Board>,   // None for the initial position
+    },
+    GameAcc {
+        name: String,
+        code: u8,
+    },
+    Move {
+        time: u32,
+        from: usize,               // Board index (square - 1)
+        to: usize,
+        captures: Vec<usize>,
+    },
+    GameEnd {
+        reason: u8,
+        stop: u8,
+    },
+    Chat(String),
+    BackReq {
+        move_number: u32,
+        color: Color,
+    },
+    BackAcc(u8),
+}
+
+fn color_char(color: Color) -> char {
+    match color {
+        Color::Red => 'W',
+        Color::Black => 'Z',
+    }
+}
+
+fn parse_color(c: char) -> Result<Color, String> {
+    match c {
+        'W' | 'w' => Ok(Color::Red),
+        'Z' | 'z' => Ok(Color::Black),
+        other => Err(format!("invalid color '{}'", other)),
+    }
+}
+
+fn pad(text: &str, width: usize) -> String {
+    format!("{:<width$.width$}", text, width = width)
+}
+
+// Fixed width field reader over a message body
+struct Fields<'a> {
+    text: &'a str,
+    pos: usize,
+    num_squares: usize,
+}
+
+impl<'a> Fields<'a> {
+    fn take(&mut self, width: usize) -> Result<&'a str, String> {
+        let end = self.pos + width;
+        let field = self.text.get(self.pos..end).ok_or("message too short")?;
+        self.pos = end;
+        Ok(field)
+    }
+
+    fn number(&mut self, width: usize) -> Result<u32, String> {
+        let field = self.take(width)?;
+        field.trim().parse().map_err(|_| format!("invalid number '{}'", field))
+    }
+
+    fn square(&mut self) -> Result<usize, String> {
+        match self.number(2)? as usize {
+            n if (1..=self.num_squares).contains(&n) => Ok(n - 1),
+            n => Err(format!("invalid square {}", n)),
+        }
+    }
+
+    fn char(&mut self) -> Result<char, String> {
+        self.take(1)?.chars().next().ok_or_else(|| "message too short".to_string())
+    }
+}
+
+impl Message {
+    pub fn encode(&self) -> String {
+        match self {
+            Message::GameReq { name, follower_color, minutes, moves, position } => {
+                let start = match position {
+                    None => "A".to_string(),
+                    Some(board) => {
+                        let squares: String = board.squares[..board.num_squares()].iter()
+                            .map(|&p| match (p.color(), p.is_king()) {
+                                (Some(Color::Red), false) => 'w',
+                                (Some(Color::Red), true) => 'W',
+                                (Some(Color::Black), false) => 'z',
+                                (Some(Color::Black), true) => 'Z',
+                                (None, _) => 'e',
+                            })
+                            .collect();
+                        format!("B{}{}", color_char(board.turn), squares)
+                    },
+                };
+                format!("R{}{}{}{:03}{:03}{}", VERSION, pad(name, 32), color_char(*follower_color), minutes, moves, start)
+            },
+            Message::GameAcc { name, code } => format!("A{}{}", pad(name, 32), code),
+            Message::Move { time, from, to, captures } => {
+                let captured: String = captures.iter().map(|c| format!("{:02}", c + 1)).collect();
+                format!("M{:04}{:02}{:02}{:02}{}", time.min(&9999), from + 1, to + 1, captures.len(), captured)
+            },
+            Message::GameEnd { reason, stop } => format!("E{}{}", reason, stop),
+            Message::Chat(text) => format!("C{}", text),
+            Message::BackReq { move_number, color } => format!("B{:03}{}", move_number, color_char(*color)),
+            Message::BackAcc(code) => format!("K{}", code),
+        }
+    }
+
+    // Squares and start positions are read as the variant's
+    pub fn decode(text: &str, variant: Variant) -> Result<Self, String> {
+        let kind = text.chars().next().ok_or("empty message")?;
+        let num_squares = variant.rules().num_squares();
+        let mut f = Fields { text, pos: 1, num_squares };
+
+        match kind {
+            'R' => {
+                let _version = f.take(2)?;
+                let name = f.take(32)?.trim_end().to_string();
+                let follower_color = parse_color(f.char()?)?;
+                let minutes = f.number(3)?;
+                let moves = f.number(3)?;
+                let position = match f.char()? {
+                    'A' => None,
+                    'B' => {
+                        let turn = parse_color(f.char()?)?;
+                        let squares = f.take(num_squares)?;
+                        let mut fen_white = Vec::new();
+                        let mut fen_black = Vec::new();
+                        for (i, c) in squares.chars().enumerate() {
+                            match c {
+                                'w' => fen_white.push(format!("{}", i + 1)),
+                                'W' => fen_white.push(format!("K{}", i + 1)),
+                                'z' => fen_black.push(format!("{}", i + 1)),
+                                'Z' => fen_black.push(format!("K{}", i + 1)),
+                                'e' => {},
+                                other => return Err(format!("invalid square '{}'", other)),
+                            }
+                        }
+                        let side = if turn == Color::Red { 'W' } else { 'B' };
+                        let fen = format!("{}:W{}:B{}", side, fen_white.join(","), fen_black.join(","));
+                        Some(Board::from_variant_fen(variant, &fen)?)
+                    },
+                    other => return Err(format!("invalid start position type '{}'", other)),
+                };
+                Ok(Message::GameReq { name, follower_color, minutes, moves, position })
+            },
+            'A' => {
+                let name = f.take(32)?.trim_end().to_string();
+                let code = f.number(1)? as u8;
+                Ok(Message::GameAcc { name, code })
+            },
+            'M' => {
+                let time = f.number(4)?;
+                let from = f.square()?;
+                let to = f.square()?;
+                let count = f.number(2)?;
+                let captures = (0..count).map(|_| f.square()).collect::<Result<Vec<usize>, String>>()?;
+                Ok(Message::Move { time, from, to, captures })
+            },
+            'E' => Ok(Message::GameEnd { reason: f.number(1)? as u8, stop: f.number(1)? as u8 }),
+            'C' => Ok(Message::Chat(text[1..].to_string())),
+            'B' => {
+                let move_number = f.number(3)?;
+                let color = parse_color(f.char()?)?;
+                Ok(Message::BackReq { move_number, color })
+            },
+            'K' => Ok(Message::BackAcc(f.number(1)? as u8)),
+            other => Err(format!("unknown message type '{}'", other)),
+        }
+    }
+
+    // DXP form of a move on the board it is played from
+    pub fn from_move(m: &Move, time: u32) -> Self {
+        Message::Move { time, from: m.from, to: m.to, captures: m.captures.to_vec() }
+    }
+}
+
+pub struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    variant: Variant,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream, variant: Variant) -> Result<Self, String> {
+        let writer = stream.try_clone().map_err(|e| e.to_string())?;
+        Ok(Connection { reader: BufReader::new(stream), writer, variant })
+    }
+
+    pub fn send(&mut self, message: &Message) -> Result<(), String> {
+        let mut bytes = message.encode().into_bytes();
+        bytes.push(0);
+        self.writer.write_all(&bytes).map_err(|e| format!("send failed: {}", e))
+    }
+
+    pub fn receive(&mut self) -> Result<Message, String> {
+        let mut bytes = Vec::new();
+        let read = self.reader.read_until(0, &mut bytes).map_err(|e| format!("receive failed: {}", e))?;
+        if read == 0 {
+            return Err("connection closed".to_string());
+        }
+        if bytes.last() == Some(&0) {
+            bytes.pop();
+        }
+        Message::decode(&String::from_utf8_lossy(&bytes), self.variant)
+    }
+
+    fn set_timeout(&self, timeout: Option<Duration>) {
+        let _ = self.reader.get_ref().set_read_timeout(timeout);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DxpConfig {
+    pub name: String,
+    pub host: String,       // Address to listen on
+    pub variant: Variant,   // Game both sides play
+    pub color: Color,       // Color we play when initiating
+    pub minutes: u32,       // Thinking time per side, proposed in GAMEREQ
+    pub moves: u32,         // Moves to play in that time
+    pub max_plies: u32,     // We claim a draw after this many plies
+}
+
+impl Default for DxpConfig {
+    fn default() -> Self {
+        DxpConfig {
+            name: crate::protocol::ENGINE_NAME.to_string(),
+            host: "127.0.0.1".to_string(),
+            variant: Variant::American,
+            color: Color::Red,
+            minutes: 5,
+            moves: 50,
+            max_plies: 300,
+        }
+    }
+}
+
+impl DxpConfig {
+    // Time per move implied by the agreed time control
+    pub fn time_per_move(&self) -> Duration {
+        Duration::from_secs(self.minutes as u64 * 60) / self.moves.max(1)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Win,
+    Loss,
+    Draw,
+    Unknown,
+}
+
+// What happened during a session, passed to the caller's on_event so it
+// can log it (Display gives a line of text) or ignore it
+#[derive(Debug, Clone)]
+pub enum Event {
+    Listening { host: String, port: u16 },
+    Connected(String),                        // Peer address
+    GameRequested { name: String, minutes: u32, moves: u32, ours: Color },
+    GameAccepted { name: String, ours: Color },
+    Played(Move),                             // Our move
+    OpponentPlayed(Move),
+    Chat(String),
+    TookBack { move_number: u32, to_move: Color },
+    Ignored(String),                          // An unexpected message, encoded
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::Listening { host, port } => write!(f, "Waiting for a DXP connection on {}:{}...", host, port),
+            Event::Connected(peer) => write!(f, "Connection from {}", peer),
+            Event::GameRequested { name, minutes, moves, ours } => {
+                write!(f, "Game request from {} ({} min / {} moves), we play {:?}", name, minutes, moves, ours)
+            },
+            Event::GameAccepted { name, ours } => write!(f, "{} accepted the game, we play {:?}", name, ours),
+            Event::Played(m) => write!(f, "We play {}", m.to_numeric()),
+            Event::OpponentPlayed(m) => write!(f, "Opponent plays {}", m.to_numeric()),
+            Event::Chat(text) => write!(f, "Chat: {}", text),
+            Event::TookBack { move_number, to_move } => write!(f, "Took back to move {} ({:?} to move)", move_number, to_move),
+            Event::Ignored(message) => write!(f, "Ignoring unexpected message: {}", message),
+        }
+    }
+}
+
+// Find the legal move matching a DXP move: same squares and the same set of
+// captured pieces (DXP doesn't give the order of a multi-jump)
+fn match_move(board: &Board, from: usize, to: usize, captures: &[usize]) -> Option<Move> {
+    let mut wanted = captures.to_vec();
+    wanted.sort_unstable();
+
+    board.get_valid_moves().into_iter().find(|m| {
+        let mut caps = m.captures.to_vec();
+        caps.sort_unstable();
+        m.from == from && m.to == to && caps == wanted
+    })
+}
+
+// Ply a BACKREQ points at: the position before `color` played move `move_number`
+fn back_ply(move_number: u32, color: Color, first: Color) -> usize {
+    let ply = move_number.saturating_sub(1) as usize * 2;
+    if color == first { ply } else { ply + 1 }
+}
+
+// Play one game after GAMEREQ/GAMEACC, choosing our moves with `choose`.
+// The side that sees the game end sends GAMEEND and the other answers it.
+pub fn play_game<F, L>(conn: &mut Connection, ours: Color, start: Board, config: &DxpConfig, mut choose: F, mut on_event: L) -> Result<Outcome, String>
+where
+    F: FnMut(&Board) -> Option<Move>,
+    L: FnMut(&Event),
+{
+    let first = start.turn;
+    let mut history = vec![start];
+    let mut ended_by_us = None;
+
+    loop {
+        let board = history.last().cloned().unwrap_or_default();
+        let valid_moves = board.get_valid_moves();
+        let ply = history.len() as u32 - 1;
+
+        // Game over for the side to move? The rules say who won; with no
+        // winner it was the ply limit.
+        if board.is_game_over() || valid_moves.is_empty() || ply >= config.max_plies {
+            let outcome = match board.winner() {
+                Some(winner) if winner == ours => Outcome::Win,
+                Some(_) => Outcome::Loss,
+                None => Outcome::Draw,
+            };
+            let reason = match outcome {
+                Outcome::Loss => 1,
+                Outcome::Draw => 2,
+                _ => 3,
+            };
+            conn.send(&Message::GameEnd { reason, stop: 1 })?;
+            ended_by_us = Some(outcome);
+        }
+
+        if let Some(outcome) = ended_by_us {
+            // Wait briefly for the opponent's GAMEEND answer
+            conn.set_timeout(Some(Duration::from_secs(5)));
+            let _ = conn.receive();
+            conn.set_timeout(None);
+            return Ok(outcome);
+        }
+
+        if board.turn == ours {
+            let start_time = Instant::now();
+            let m = choose(&board).ok_or("engine found no move")?;
+            let mut next = board.clone();
+            next.make_move(&m)?;
+
+            conn.send(&Message::from_move(&m, start_time.elapsed().as_secs() as u32))?;
+            on_event(&Event::Played(m));
+            history.push(next);
+            continue;
+        }
+
+        match conn.receive()? {
+            Message::Move { from, to, captures, .. } => {
+                let m = match_move(&board, from, to, &captures)
+                    .ok_or_else(|| format!("opponent sent an illegal move {}-{}", from + 1, to + 1))?;
+                let mut next = board.clone();
+                next.make_move(&m)?;
+                on_event(&Event::OpponentPlayed(m));
+                history.push(next);
+            },
+            Message::GameEnd { reason, .. } => {
+                // Answer with our own view of the result
+                let outcome = match reason {
+                    1 => Outcome::Win,   // They lose
+                    2 => Outcome::Draw,
+                    3 => Outcome::Loss,  // They win
+                    _ => Outcome::Unknown,
+                };
+                let our_reason = match outcome {
+                    Outcome::Win => 3,
+                    Outcome::Loss => 1,
+                    Outcome::Draw => 2,
+                    Outcome::Unknown => 0,
+                };
+                conn.send(&Message::GameEnd { reason: our_reason, stop: 1 })?;
+                return Ok(outcome);
+            },
+            Message::Chat(text) => on_event(&Event::Chat(text)),
+            Message::BackReq { move_number, color } => {
+                let target = back_ply(move_number, color, first);
+                if target < history.len() && history[target].turn == color {
+                    history.truncate(target + 1);
+                    conn.send(&Message::BackAcc(0))?;
+                    on_event(&Event::TookBack { move_number, to_move: color });
+                } else {
+                    conn.send(&Message::BackAcc(2))?;
+                }
+            },
+            other => on_event(&Event::Ignored(other.encode())),
+        }
+    }
+}
+
+// Wait for one opponent on config.host and `port`, accept its game request
+// and play it
+pub fn listen<F, L>(port: u16, config: &DxpConfig, choose: F, mut on_event: L) -> Result<Outcome, String>
+where
+    F: FnMut(&Board) -> Option<Move>,
+    L: FnMut(&Event),
+{
+    let listener = TcpListener::bind((config.host.as_str(), port))
+        .map_err(|e| format!("could not listen on {}:{}: {}", config.host, port, e))?;
+    on_event(&Event::Listening { host: config.host.clone(), port });
+
+    let (stream, peer) = listener.accept().map_err(|e| e.to_string())?;
+    on_event(&Event::Connected(peer.to_string()));
+    let mut conn = Connection::new(stream, config.variant)?;
+
+    loop {
+        match conn.receive()? {
+            Message::GameReq { name, follower_color, minutes, moves, position } => {
+                on_event(&Event::GameRequested { name, minutes, moves, ours: follower_color });
+                conn.send(&Message::GameAcc { name: config.name.clone(), code: 0 })?;
+
+                let config = DxpConfig { minutes, moves, ..config.clone() };
+                let start = position.unwrap_or_else(|| Board::for_variant(config.variant));
+                return play_game(&mut conn, follower_color, start, &config, choose, on_event);
+            },
+            Message::Chat(text) => on_event(&Event::Chat(text)),
+            other => on_event(&Event::Ignored(other.encode())),
+        }
+    }
+}
+
+// Connect to a DXP server, request a game and play it
+pub fn connect<A, F, L>(address: A, config: &DxpConfig, choose: F, mut on_event: L) -> Result<Outcome, String>
+where
+    A: ToSocketAddrs,
+    F: FnMut(&Board) -> Option<Move>,
+    L: FnMut(&Event),
+{
+    let stream = TcpStream::connect(address).map_err(|e| format!("could not connect: {}", e))?;
+    let mut conn = Connection::new(stream, config.variant)?;
+
+    conn.send(&Message::GameReq {
+        name: config.name.clone(),
+        follower_color: config.color.toggle(),
+        minutes: config.minutes,
+        moves: config.moves,
+        position: None,
+    })?;
+
+    match conn.receive()? {
+        Message::GameAcc { name, code: 0 } => {
+            on_event(&Event::GameAccepted { name, ours: config.color });
+            play_game(&mut conn, config.color, Board::for_variant(config.variant), config, choose, on_event)
+        },
+        Message::GameAcc { name, code } => Err(format!("{} refused the game (code {})", name, code)),
+        other => Err(format!("expected GAMEACC, got {}", other.encode())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn international_messages_round_trip() {
+        let board = Board::from_variant_fen(Variant::International, "B:W31-50:B1-20").unwrap();
+        let request = Message::GameReq {
+            name: "test".to_string(),
+            follower_color: Color::Black,
+            minutes: 10,
+            moves: 75,
+            position: Some(board.clone()),
+        };
+        let text = request.encode();
+        assert_eq!(text.len(), 1 + 2 + 32 + 1 + 3 + 3 + 2 + 50);
+
+        match Message::decode(&text, Variant::International).unwrap() {
+            Message::GameReq { position: Some(decoded), .. } => assert_eq!(decoded.to_fen(), board.to_fen()),
+            other => panic!("decoded {:?}", other),
+        }
+        assert!(Message::decode(&text, Variant::American).is_err());
+
+        let jump = Message::Move { time: 3, from: 49, to: 38, captures: vec![43] };
+        assert_eq!(jump.encode(), "M000350390144");
+        match Message::decode(&jump.encode(), Variant::International).unwrap() {
+            Message::Move { from, to, captures, .. } => assert_eq!((from, to, captures), (49, 38, vec![43])),
+            other => panic!("decoded {:?}", other),
+        }
+        assert!(Message::decode(&jump.encode(), Variant::American).is_err());
+    }
+
+    #[test]
+    fn giveaway_games_end_with_the_rules_winner() {
+        // Red's 22-18 forces 15x22, and Red wins by having no pieces left
+        let config = DxpConfig { variant: Variant::Giveaway, ..DxpConfig::default() };
+        let start = Board::from_variant_fen(Variant::Giveaway, "W:W22:B15").unwrap();
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let black = {
+            let (config, start) = (config.clone(), start.clone());
+            std::thread::spawn(move || {
+                let (stream, _) = listener.accept().unwrap();
+                let mut conn = Connection::new(stream, Variant::Giveaway).unwrap();
+                let choose = |board: &Board| board.get_valid_moves().into_iter().next();
+                play_game(&mut conn, Color::Black, start, &config, choose, |_| {})
+            })
+        };
+
+        let mut conn = Connection::new(TcpStream::connect(address).unwrap(), Variant::Giveaway).unwrap();
+        let mut events = Vec::new();
+        let red = play_game(&mut conn, Color::Red, start, &config, |_| Some(Move::new(21, 17)), |event| events.push(event.to_string()));
+        assert_eq!(red, Ok(Outcome::Win));
+        assert_eq!(black.join().unwrap(), Ok(Outcome::Loss));
+        assert_eq!(events, ["We play 22-18", "Opponent plays 15x22"]);
+    }
+}
@@ -1,226 +1,204 @@
-use crate::board::{Board, Color};
-use crate::mv::{Move, is_valid_move};
+use crate::board::{Board, Color, Piece};
+use crate::mv::{Move, SquareList, crowning_square};
 use rayon::prelude::*;
 
-pub struct MoveEvaluator {
-    pub board: Board,
+// Direction vectors To check around the peice
+const DIRECTIONS: [(i32, i32); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+
+pub struct MoveEvaluator<'a> {
+    pub board: &'a Board,
 }
 
-impl MoveEvaluator {
-    pub fn new(board: Board) -> Self {
+impl<'a> MoveEvaluator<'a> {
+    pub fn new(board: &'a Board) -> Self {
         MoveEvaluator { board }
     }
 
-    fn curr_piece(&self, piece: char) -> bool {
-        match self.board.turn {
-            Color::Red => piece == 'r' || piece == 'R',
-            Color::Black => piece == 'b' || piece == 'B',
-        }
+    fn curr_piece(&self, piece: Piece) -> bool {
+        piece.color() == Some(self.board.turn)
     }
 
-    // Generate potential capture moves for a piece
-    fn cap_moves(&self, index: usize) -> Vec<Move> {
-        let mut potential_captures = Vec::new();
-        let piece = self.board.squares[index];
-
-        if !self.curr_piece(piece) {
-            return potential_captures;
+    // Row direction the side to move's men advance in
+    fn forward(&self) -> i32 {
+        match self.board.turn {
+            Color::Red => -1,
+            Color::Black => 1,
         }
+    }
 
+    // Squares along a diagonal from index, nearest first
+    fn ray(&self, index: usize, dr: i32, dc: i32) -> impl Iterator<Item = usize> + '_ {
         let (row, col) = self.board.index_to_coords(index);
+        (1..).map_while(move |k| self.board.coords_to_index(row as i32 + k * dr, col as i32 + k * dc))
+    }
 
-        // Direction vectors To check around the peice
-        let dir = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
-
-        for &(dr, dc) in &dir {
-            // For capture moves (2 squares)
-            let capture_row = (row as i32) + dr;
-            let capture_col = (col as i32) + dc;
-            let land_row = (row as i32) + 2 * dr;
-            let land_col = (col as i32) + 2 * dc;
-
-            if let (Some(capture_idx), Some(land_idx)) = (
-                self.board.coords_to_index(capture_row, capture_col),
-                self.board.coords_to_index(land_row, land_col)
-            ) {
-                let capture_move = Move::new(index, land_idx, vec![capture_idx]);
-                if is_valid_move(&self.board, &capture_move) {
-                    potential_captures.push(capture_move);
-                }
-            }
-        }
+    // The moving piece has left its origin, so that square counts as empty
+    fn is_free(&self, index: usize, origin: usize) -> bool {
+        self.board.squares[index].is_empty() || index == origin
+    }
 
-        potential_captures
+    // How far along a diagonal the piece may travel
+    fn reach(&self, piece: Piece) -> usize {
+        if piece.is_king() && self.board.rules().flying_kings() { usize::MAX } else { 1 }
     }
 
     // Generate potential regular
-    fn reg_moves(&self, index: usize) -> Vec<Move> {
-        let mut potential_moves = Vec::new();
+    fn reg_moves(&self, index: usize, moves: &mut Vec<Move>) {
         let piece = self.board.squares[index];
 
         if !self.curr_piece(piece) {
-            return potential_moves;
-        }
-
-        let (row, col) = self.board.index_to_coords(index);
-
-        let dir = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
-
-        for &(dr, dc) in &dir {
-            let target_row = (row as i32) + dr;
-            let target_col = (col as i32) + dc;
-
-            if let Some(target_idx) = self.board.coords_to_index(target_row, target_col) {
-                let regular_move = Move::new(index, target_idx, Vec::new());
-                if is_valid_move(&self.board, &regular_move) {
-                    potential_moves.push(regular_move);
-                }
-            }
+            return;
         }
 
-        potential_moves
+        DIRECTIONS.iter()
+            .filter(|&&(dr, _)| piece.is_king() || dr == self.forward())
+            .for_each(|&(dr, dc)| {
+                moves.extend(self.ray(index, dr, dc)
+                    .take_while(|&target| self.board.squares[target].is_empty())
+                    .take(self.reach(piece))
+                    .map(|target| Move::new(index, target)));
+            });
     }
 
-    // Generate and check potential multi-capture moves
-    fn find_mult_cap(&self, from_idx: usize, visited: &mut Vec<usize>,
-                     current_path: &mut Vec<usize>, current_captures: &mut Vec<usize>,
+    // Extend the capture sequence in path/captures from its last square.
+    // Captured pieces stay on the board until the move is over, so they
+    // block the way and can't be jumped twice. `piece` is the moving piece
+    // as it jumps on, which is a king once a man is crowned mid-capture.
+    fn find_mult_cap(&self, piece: Piece, path: &mut SquareList, captures: &mut SquareList,
                      all_moves: &mut Vec<Move>) {
-        let (row, col) = self.board.index_to_coords(from_idx);
-
-        // Direction vectors (row_delta, col_delta) for all 4 diagonal directions
-        let directions = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
-
-        for &(dr, dc) in &directions {
-            // Check potential capture in this direction
-            let capture_row = (row as i32) + dr;
-            let capture_col = (col as i32) + dc;
-            let land_row = (row as i32) + 2 * dr;
-            let land_col = (col as i32) + 2 * dc;
-
-            if let (Some(capture_idx), Some(land_idx)) = (
-                self.board.coords_to_index(capture_row, capture_col),
-                self.board.coords_to_index(land_row, land_col)
-            ) {
-                // Skip if we've already visited this square or captured this piece
-                if visited.contains(&land_idx) || current_captures.contains(&capture_idx) {
-                    continue;
-                }
-
-                // Create move and check if it's valid
-                let mut new_path = current_path.clone();
-                new_path.push(land_idx);
-
-                let mut new_captures = current_captures.clone();
-                new_captures.push(capture_idx);
-
-                let multi_move = Move::with_path(
-                    current_path[0],  // Starting position
-                    land_idx,         // Current landing position
-                    new_captures.clone(),
-                    new_path.clone()
-                );
-
-                // Check if this move would be valid
-                if is_valid_move(&self.board, &multi_move) {
-                    // Valid move found, add to list
-                    all_moves.push(multi_move);
-
-                    // Continue searching for more captures from new position
-                    let mut new_visited = visited.clone();
-                    new_visited.push(land_idx);
-
-                    self.find_mult_cap(land_idx, &mut new_visited, &mut new_path,
-                                       &mut new_captures, all_moves);
-                }
+        let (Some(origin), Some(from_idx)) = (path.first(), path.last()) else {
+            return;
+        };
+        let reach = self.reach(piece);
+        let backward = piece.is_king() || self.board.rules().men_capture_backward();
+
+        for &(dr, dc) in &DIRECTIONS {
+            if !backward && dr != self.forward() {
+                continue;
             }
-        }
-    }
 
-    // Sequential implementation to calculate all valid moves
-    pub fn seq_possible_moves(&self) -> Vec<Move> {
-        // Start by checking for multi-captures
-        let mut mult_cap = Vec::new();
+            // Flying kings may pass over empty squares to the piece they take
+            let mut line = self.ray(from_idx, dr, dc);
+            let Some((gap, capture_idx)) = line.by_ref().enumerate().find(|&(_, sq)| !self.is_free(sq, origin)) else {
+                continue;
+            };
+            if gap > 0 && reach == 1 {
+                continue;
+            }
 
-        for i in 0..32 {
-            if !self.curr_piece(self.board.squares[i]) {
+            let target = self.board.squares[capture_idx];
+            if target.color() != Some(self.board.turn.toggle())
+                || captures.contains(capture_idx)
+                || (target.is_king() && !piece.is_king() && !self.board.rules().men_capture_kings()) {
                 continue;
             }
 
-            let mut visited = vec![i];
-            let mut current_path = vec![i];
-            let mut curr_cap = Vec::new();
+            // The line carries on from the square behind the captured piece
+            for land_idx in line.take_while(|&sq| self.is_free(sq, origin)).take(reach) {
+                path.push(land_idx);
+                captures.push(capture_idx);
+
+                let crowned = self.board.rules().promotes_mid_capture()
+                    && crowning_square(self.board, piece, land_idx);
+                let next = if crowned { piece.crowned() } else { piece };
+
+                // A jump sequence has to be completed, so this move only
+                // counts if it can't be extended
+                let found = all_moves.len();
+                self.find_mult_cap(next, path, captures, all_moves);
+                if all_moves.len() == found {
+                    all_moves.push(Move::with_path(origin, land_idx, *captures, *path));
+                }
 
-            self.find_mult_cap(i, &mut visited, &mut current_path,
-                               &mut curr_cap, &mut mult_cap);
+                path.pop();
+                captures.pop();
+            }
         }
+    }
 
-        if !mult_cap.is_empty() {
-            return mult_cap;
+    // Every complete capture sequence of the piece on index
+    fn cap_moves(&self, index: usize, moves: &mut Vec<Move>) {
+        let piece = self.board.squares[index];
+        if self.curr_piece(piece) {
+            let mut path = SquareList::new();
+            path.push(index);
+            self.find_mult_cap(piece, &mut path, &mut SquareList::new(), moves);
         }
+    }
 
-        // If no multi-captures, check for single captures
-        let mut all_cap = Vec::new();
-
-        for i in 0..32 {
-            let captures = self.cap_moves(i);
-            all_cap.extend(captures);
+    // Apply the capture obligations to the captures from `start` on: drop
+    // sequences that take the same pieces to the same square by another
+    // route, and keep only the best ranked under the board's capture priority
+    fn capture_rules(&self, moves: &mut Vec<Move>, start: usize) {
+        let same = |a: &Move, b: &Move| a.from == b.from && a.to == b.to
+            && a.captures.len() == b.captures.len()
+            && a.captures.iter().all(|c| b.captures.contains(c));
+
+        let mut unique = start;
+        for i in start..moves.len() {
+            if !moves[start..unique].iter().any(|kept| same(kept, &moves[i])) {
+                moves.swap(unique, i);
+                unique += 1;
+            }
         }
+        moves.truncate(unique);
+
+        let rules = self.board.rules();
+        if let Some(best) = moves[start..].iter().map(|m| rules.capture_rank(self.board, m)).max() {
+            let mut index = 0;
+            moves.retain(|m| {
+                index += 1;
+                index <= start || rules.capture_rank(self.board, m) == best
+            });
+        }
+    }
 
-        if !all_cap.is_empty() {
-            return all_cap;
+    // Append every legal move for the side to move to `moves`. Once the list
+    // has grown to the size it needs this allocates nothing, so callers keep
+    // one list per ply and clear it between positions.
+    pub fn generate(&self, moves: &mut Vec<Move>) {
+        let start = moves.len();
+        let squares = 0..self.board.num_squares();
+
+        // Captures are compulsory
+        squares.clone().for_each(|i| self.cap_moves(i, moves));
+        if moves.len() > start {
+            self.capture_rules(moves, start);
+            return;
         }
 
         // Only if no captures are available, generate regular moves
-        let mut reg_moves = Vec::new();
-
-        for i in 0..32 {
-            let moves = self.reg_moves(i);
-            reg_moves.extend(moves);
-        }
+        squares.for_each(|i| self.reg_moves(i, moves));
+    }
 
-        reg_moves
+    // Sequential implementation to calculate all valid moves
+    pub fn seq_possible_moves(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
+        self.generate(&mut moves);
+        moves
     }
+
     // Parallel implementation to calculate all valid moves
     pub fn par_possible_moves(&self) -> Vec<Move> {
-        // Start by checking for multi-captures (sequential due to recursive nature)
-        let mut mult_cap = Vec::new();
-
-        for i in 0..32 {
-            if !self.curr_piece(self.board.squares[i]) {
-                continue;
-            }
-
-            let mut visited = vec![i];
-            let mut current_path = vec![i];
-            let mut current_captures = Vec::new();
-
-            self.find_mult_cap(i, &mut visited, &mut current_path,
-                               &mut current_captures, &mut mult_cap);
-        }
-
-        if !mult_cap.is_empty() {
-            return mult_cap;
-        }
-
-        // If no multi-captures, check for single captures in parallel
-        let all_indices: Vec<usize> = (0..32).collect();
-
-        let all_captures: Vec<Move> = all_indices.par_iter()
-            .map(|&idx| self.cap_moves(idx))
-            .flatten()
-            .collect();
-
+        let per_square = |generate: fn(&Self, usize, &mut Vec<Move>)| {
+            (0..self.board.num_squares()).into_par_iter()
+                .flat_map_iter(|idx| {
+                    let mut moves = Vec::new();
+                    generate(self, idx, &mut moves);
+                    moves
+                })
+                .collect::<Vec<Move>>()
+        };
+
+        // Captures are compulsory; each piece's sequences are searched in parallel
+        let mut all_captures = per_square(Self::cap_moves);
         if !all_captures.is_empty() {
+            self.capture_rules(&mut all_captures, 0);
             return all_captures;
         }
 
         // Only if no captures are available, generate regular moves in parallel
-        let all_regular_moves: Vec<Move> = all_indices.par_iter()
-            .map(|&idx| self.reg_moves(idx))
-            .flatten()
-            .collect();
-
-        all_regular_moves
+        per_square(Self::reg_moves)
     }
 
     // pub fn benchmark_performance(&self) -> (std::time::Duration, std::time::Duration) {
@@ -0,0 +1,66 @@
+use crate::ab_ai::bar_with;
+use crate::board::{Board, Color};
+use crate::nnue::Network;
+use crate::weights::EvalWeights;
+
+// Evaluation score; positive is good for the side to move
+pub type Score = f32;
+
+// A position evaluator the searches can be run with. Scores are relative to
+// the side to move so searches can use negamax without caring about colors.
+pub trait Evaluator: Sync {
+    fn evaluate(&self, board: &Board) -> Score;
+
+    // A network whose accumulator the search can update move by move for
+    // this board (see nnue::AccumulatorStack), instead of calling evaluate()
+    fn network(&self, _board: &Board) -> Option<&Network> {
+        None
+    }
+}
+
+// Flip a score given from Red's point of view to the side to move
+pub fn relative(board: &Board, red_score: f32) -> Score {
+    match board.turn {
+        Color::Red => red_score,
+        Color::Black => -red_score,
+    }
+}
+
+// The hand-written eval bar with its (possibly tuned) weights
+#[derive(Debug, Clone, Default)]
+pub struct Heuristic {
+    pub weights: EvalWeights,
+}
+
+impl Heuristic {
+    pub fn new(weights: EvalWeights) -> Self {
+        Heuristic { weights }
+    }
+}
+
+impl Evaluator for Heuristic {
+    fn evaluate(&self, board: &Board) -> Score {
+        relative(board, bar_with(board, &self.weights))
+    }
+}
+
+// Baseline that only counts material, useful to measure what the positional
+// terms are worth. In giveaway material counts against you.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MaterialOnly;
+
+impl Evaluator for MaterialOnly {
+    fn evaluate(&self, board: &Board) -> Score {
+        let red_score: f32 = board.squares.iter()
+            .filter_map(|&piece| {
+                let value = if piece.is_king() { 2.5 } else { 1.0 };
+                match piece.color()? {
+                    Color::Red => Some(value),
+                    Color::Black => Some(-value),
+                }
+            })
+            .sum();
+
+        relative(board, if board.rules().reversed_goal() { -red_score } else { red_score })
+    }
+}
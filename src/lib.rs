@@ -0,0 +1,25 @@
+pub mod allocations;
+pub mod board;
+pub mod datagen;
+pub mod dxp;
+pub mod mv;
+pub mod eval_moves;
+pub mod ab_ai;
+pub mod ai;
+pub mod evaluator;
+pub mod mcts;
+pub mod nnue;
+pub mod perft;
+pub mod player;
+pub mod protocol;
+pub mod render;
+pub mod rng;
+pub mod rules;
+pub mod script;
+pub mod search;
+pub mod server;
+pub mod tt;
+pub mod tui;
+pub mod variant;
+pub mod weights;
+pub mod tune;
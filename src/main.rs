@@ -1,135 +1,880 @@
-mod board;
-mod mv;
+mod cli;
 
 use std::io::{self, Write};
-use board::{Board, Color};
-
-fn process_move(board: &mut Board, input: &str) -> bool {
-    // Quit command
-    if input.to_lowercase() == "q" {
-        println!("Thanks for playing!");
-        return false;
-    }
-
-    // Parse the move
-    let move_result = mv::Move::from_notation(input, board);
-    match move_result {
-        Some(m) => {
-            // Check if the move is valid
-            if mv::is_valid_move(board, &m) {
-                println!("Executing move: {} with {} captures",
-                         m.to_notation(board),
-                         m.captures.len());
-
-                // Execute the move
-                match board.make_move(&m) {
-                    Ok(_) => {},
-                    Err(e) => {
-                        println!("Error executing move: {}", e);
-                    }
-                }
-            } else {
-                println!("Invalid move! Possible reasons:");
-                println!("- Not your piece");
-                println!("- Destination not empty");
-                println!("- Not moving diagonally");
-                println!("- Pawn moving in wrong direction");
-                println!("- Invalid capture");
-                return true; // Continue the game even if move is invalid
-            }
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+use checkers_bot::ab_ai::AlphaBetaEngine;
+#[cfg(feature = "count-allocs")]
+use checkers_bot::allocations;
+use checkers_bot::board::{Board, Color};
+use checkers_bot::datagen::{self, DataFormat, DatagenConfig};
+use checkers_bot::dxp::{self, DxpConfig};
+use checkers_bot::evaluator::{Evaluator, Heuristic, MaterialOnly};
+use checkers_bot::mv::{self, Notation};
+use checkers_bot::nnue::NnueEvaluator;
+use checkers_bot::perft;
+use checkers_bot::player::{self, EnginePlayer, GameResult, PlayerKind};
+use checkers_bot::render::{self, Renderer};
+use checkers_bot::rng::Rng;
+use checkers_bot::script::Script;
+use checkers_bot::search::{SearchLimits, SearchResult};
+use checkers_bot::server::{self, ServeConfig};
+use checkers_bot::tui::{self, TuiConfig};
+use checkers_bot::tune::{self, TuneConfig};
+use checkers_bot::variant::Variant;
+use checkers_bot::weights::EvalWeights;
+use cli::{CliError, Command, Flag, Matches};
+
+// Counting costs an atomic add per allocation, so it is only built in for
+// checking that perft doesn't allocate per node
+#[cfg(feature = "count-allocs")]
+#[global_allocator]
+static ALLOCATOR: allocations::Counting = allocations::Counting;
+
+// Flags shared by the commands that run an engine
+const ENGINE: Flag = Flag { name: "engine", value: Some("NAME"), help: "alphabeta, mcts or parallel (default alphabeta)" };
+const DEPTH: Flag = Flag { name: "depth", value: Some("N"), help: "Search depth" };
+const TIME: Flag = Flag { name: "time", value: Some("MS"), help: "Thinking time per move in milliseconds" };
+const THREADS: Flag = Flag { name: "threads", value: Some("N"), help: "Worker threads (default: all cores)" };
+const HASH: Flag = Flag { name: "hash", value: Some("MB"), help: "Transposition table size (default 64)" };
+const FEN: Flag = Flag { name: "fen", value: Some("FEN"), help: "Start position, e.g. \"W:W21-32:B1-12\"" };
+const NOTATION: Flag = Flag { name: "notation", value: Some("STYLE"), help: "Move output: numeric or algebraic (default numeric)" };
+const GAMES: Flag = Flag { name: "games", value: Some("N"), help: "Number of games" };
+const RANDOM_PLIES: Flag = Flag { name: "random-plies", value: Some("N"), help: "Random opening moves per game" };
+const SEED: Flag = Flag { name: "seed", value: Some("N"), help: "Seed for the random openings" };
+const BOARD: Flag = Flag { name: "board", value: Some("STYLE"), help: "Diagrams: ascii or unicode, plus numbers or flipped (e.g. unicode,numbers)" };
+const PONDER: Flag = Flag { name: "ponder", value: None, help: "Let engines think on the opponent's time" };
+const VARIANT: Flag = Flag { name: "variant", value: Some("RULES"), help: "american, international, russian, brazilian, italian, spanish, pool, czech or giveaway" };
+const MAX_PLIES: Flag = Flag { name: "max-plies", value: Some("N"), help: "Call the game a draw after N plies (default 300)" };
+const EVAL: Flag = Flag { name: "eval", value: Some("NAME"), help: "heuristic, material or nnue (default heuristic)" };
+const NETWORK: Flag = Flag { name: "network", value: Some("PATH"), help: "Network file for --eval nnue" };
+const WEIGHTS: Flag = Flag { name: "weights", value: Some("PATH"), help: "Evaluation weights file for --eval heuristic" };
+
+const COMMANDS: &[Command] = &[
+    Command {
+        name: "play",
+        args: "",
+        about: "Play a game in the terminal (the default command)",
+        flags: &[
+            Flag { name: "red", value: Some("PLAYER"), help: "human, alphabeta, mcts or parallel" },
+            Flag { name: "black", value: Some("PLAYER"), help: "human, alphabeta, mcts or parallel" },
+            Flag { name: "color", value: Some("COLOR"), help: "Your color against --engine: red or black" },
+            ENGINE, DEPTH, TIME, THREADS, HASH, EVAL, NETWORK, WEIGHTS, VARIANT, FEN, NOTATION, BOARD, MAX_PLIES, PONDER,
+        ],
+    },
+    Command {
+        name: "tui",
+        args: "",
+        about: "Play in a full-screen terminal UI",
+        flags: &[
+            Flag { name: "red", value: Some("PLAYER"), help: "human, alphabeta, mcts or parallel" },
+            Flag { name: "black", value: Some("PLAYER"), help: "human, alphabeta, mcts or parallel" },
+            Flag { name: "color", value: Some("COLOR"), help: "Your color against --engine: red or black" },
+            ENGINE, DEPTH, TIME, THREADS, HASH, VARIANT, FEN, NOTATION, PONDER,
+        ],
+    },
+    Command {
+        name: "simulate",
+        args: "<script>",
+        about: "Replay a move list or PDN file and check its expectations",
+        flags: &[
+            NOTATION, BOARD,
+            Flag { name: "boards", value: None, help: "Show the board after every move" },
+            Flag { name: "quiet", value: None, help: "Only report the outcome" },
+        ],
+    },
+    Command {
+        name: "analyze",
+        args: "",
+        about: "Search one position and print the analysis",
+        flags: &[
+            ENGINE, DEPTH, TIME, THREADS, HASH, EVAL, NETWORK, WEIGHTS, VARIANT, FEN, NOTATION,
+            Flag { name: "moves", value: Some("\"M...\""), help: "Moves to play from the start position first" },
+        ],
+    },
+    Command {
+        name: "perft",
+        args: "",
+        about: "Count the leaf positions of the move tree",
+        flags: &[
+            Flag { name: "depth", value: Some("N"), help: "Depth to count (default 6)" },
+            VARIANT, FEN, THREADS, NOTATION,
+            Flag { name: "divide", value: None, help: "Show the count below every root move" },
+            Flag { name: "suite", value: None, help: "Check the reference positions of --variant (or of every variant)" },
+        ],
+    },
+    Command {
+        name: "match",
+        args: "",
+        about: "Play two engines against each other and report the score",
+        flags: &[
+            ENGINE,
+            Flag { name: "opponent", value: Some("NAME"), help: "Second engine (default mcts)" },
+            Flag { name: "games", value: Some("N"), help: "Number of games, colors alternate (default 10)" },
+            EVAL, NETWORK, WEIGHTS,
+            Flag { name: "opponent-eval", value: Some("NAME"), help: "Evaluator of the second engine" },
+            Flag { name: "opponent-network", value: Some("PATH"), help: "Network file of the second engine" },
+            Flag { name: "opponent-weights", value: Some("PATH"), help: "Evaluation weights file of the second engine" },
+            DEPTH, TIME, THREADS, HASH, VARIANT, FEN, RANDOM_PLIES, SEED, MAX_PLIES, PONDER,
+        ],
+    },
+    Command {
+        name: "bench",
+        args: "",
+        about: "Search a fixed set of positions and report nodes per second",
+        flags: &[
+            Flag { name: "depth", value: Some("N"), help: "Search depth (default 8)" },
+            THREADS, HASH, EVAL, NETWORK, WEIGHTS,
+        ],
+    },
+    Command {
+        name: "selfplay",
+        args: "",
+        about: "Let an engine play itself and print the games",
+        flags: &[ENGINE, GAMES, DEPTH, TIME, THREADS, HASH, EVAL, NETWORK, WEIGHTS, VARIANT, FEN, NOTATION, RANDOM_PLIES, SEED, MAX_PLIES],
+    },
+    Command {
+        name: "protocol",
+        args: "",
+        about: "Speak the Hub-style engine protocol on stdin/stdout",
+        flags: &[],
+    },
+    Command {
+        name: "dxp",
+        args: "listen | connect HOST:PORT",
+        about: "Play one game against another engine over DXP",
+        flags: &[
+            Flag { name: "port", value: Some("N"), help: "Port to listen on (default 27531)" },
+            Flag { name: "host", value: Some("ADDR"), help: "Address to listen on (default 127.0.0.1, 0.0.0.0 for all)" },
+            Flag { name: "color", value: Some("COLOR"), help: "Our color when connecting: red or black" },
+            Flag { name: "minutes", value: Some("N"), help: "Thinking time to propose (default 5)" },
+            Flag { name: "moves", value: Some("N"), help: "Moves in that time (default 50)" },
+            TIME, THREADS, HASH, VARIANT, PONDER,
+        ],
+    },
+    Command {
+        name: "serve",
+        args: "",
+        about: "Serve a board UI and JSON API on localhost",
+        flags: &[
+            Flag { name: "port", value: Some("N"), help: "Port to listen on (default 8080)" },
+            Flag { name: "host", value: Some("ADDR"), help: "Address to bind (default 127.0.0.1)" },
+            Flag { name: "engine", value: Some("NAME"), help: "Default engine: alphabeta, mcts or parallel" },
+            Flag { name: "time", value: Some("MS"), help: "Default thinking time per move (default 1000)" },
+            DEPTH, THREADS, HASH, VARIANT, FEN, NOTATION,
+        ],
+    },
+    Command {
+        name: "tune",
+        args: "<positions> <output> [initial-weights]",
+        about: "Fit the evaluation weights to datagen positions or PDN games",
+        flags: &[VARIANT, THREADS],
+    },
+    Command {
+        name: "datagen",
+        args: "",
+        about: "Generate self-play training data",
+        flags: &[
+            Flag { name: "games", value: Some("N"), help: "Games to play (default 100)" },
+            Flag { name: "nodes", value: Some("N"), help: "Search nodes per move (default 5000)" },
+            RANDOM_PLIES,
+            Flag { name: "max-plies", value: Some("N"), help: "Score longer games as draws (default 200)" },
+            SEED,
+            Flag { name: "out", value: Some("PATH"), help: "Output file (default datagen.csv)" },
+            Flag { name: "format", value: Some("FORMAT"), help: "csv or bin" },
+            Flag { name: "weights", value: Some("PATH"), help: "Evaluation weights file" },
+            Flag { name: "resume", value: None, help: "Continue an interrupted run" },
+            THREADS,
+        ],
+    },
+];
+
+fn usage_error(message: String) -> CliError {
+    CliError::Usage(message)
+}
+
+fn set_threads(m: &Matches) -> Result<(), CliError> {
+    if let Some(threads) = m.parse::<usize>("threads")? {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads.max(1))
+            .build_global()
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+// The --fen position, or the start position, under the --variant rules
+fn start_board(m: &Matches) -> Result<Board, CliError> {
+    let variant = match m.value("variant") {
+        Some(name) => Variant::from_name(name).ok_or_else(|| usage_error(format!("unknown variant '{}'", name)))?,
+        None => Variant::American,
+    };
+    match m.value("fen") {
+        Some(fen) => Board::from_variant_fen(variant, fen).map_err(|e| usage_error(format!("invalid --fen: {}", e))),
+        None => Ok(Board::for_variant(variant)),
+    }
+}
+
+fn notation(m: &Matches) -> Result<Notation, CliError> {
+    match m.value("notation") {
+        Some(name) => Notation::from_name(name).ok_or_else(|| usage_error(format!("unknown notation '{}'", name))),
+        None => Ok(Notation::Numeric),
+    }
+}
+
+fn renderer(m: &Matches) -> Result<Renderer, CliError> {
+    Renderer::from_spec(m.value("board").unwrap_or("ascii")).map_err(usage_error)
+}
+
+fn limits(m: &Matches) -> Result<SearchLimits, CliError> {
+    Ok(SearchLimits {
+        depth: m.parse("depth")?,
+        movetime: m.parse::<u64>("time")?.map(Duration::from_millis),
+        ..Default::default()
+    })
+}
+
+fn player_kind(name: &str, allow_human: bool) -> Result<PlayerKind, CliError> {
+    match PlayerKind::from_name(name) {
+        Some(PlayerKind::Human) if !allow_human => Err(usage_error("an engine is needed here, not a human".to_string())),
+        Some(kind) => Ok(kind),
+        None => Err(usage_error(format!("unknown player '{}'", name))),
+    }
+}
+
+// The evaluator chosen by --eval, --network and --weights, or by their
+// --opponent- forms when `side` is "opponent-"
+fn evaluator(m: &Matches, side: &str) -> Result<Box<dyn Evaluator + Send>, CliError> {
+    let value = |name: &str| m.value(&format!("{}{}", side, name));
+    let name = value("eval").unwrap_or("heuristic");
+    if name != "heuristic" && value("weights").is_some() {
+        return Err(usage_error(format!("--{}weights only applies to --{}eval heuristic", side, side)));
+    }
+
+    match name {
+        "heuristic" => {
+            let weights = match value("weights") {
+                Some(path) => EvalWeights::load(path).map_err(|e| format!("Error loading weights: {}", e))?,
+                None => EvalWeights::default(),
+            };
+            Ok(Box::new(Heuristic::new(weights)))
+        },
+        "material" => Ok(Box::new(MaterialOnly)),
+        "nnue" => match value("network") {
+            Some(path) => Ok(Box::new(NnueEvaluator::load(path).map_err(|e| format!("Error loading network: {}", e))?)),
+            None => Err(usage_error(format!("--{}eval nnue needs --{}network", side, side))),
         },
-        None => {
-            println!("Invalid notation! Please use format like 'B3-D5'");
-            println!("For captures, use the destination after the jump, not the captured piece location");
-            return true; // Continue the game even if notation is invalid
+        other => Err(usage_error(format!("unknown evaluator '{}', expected heuristic, material or nnue", other))),
+    }
+}
+
+fn engine(m: &Matches, flag: &str, side: &str, default: PlayerKind) -> Result<EnginePlayer, CliError> {
+    let kind = match m.value(flag) {
+        Some(name) => player_kind(name, false)?,
+        None => default,
+    };
+    Ok(EnginePlayer::new(kind, limits(m)?, m.parse_or("hash", 64)?)
+        .with_evaluator(evaluator(m, side)?)
+        .with_ponder(m.is_set("ponder")))
+}
+
+fn color(name: &str) -> Result<Color, CliError> {
+    match name {
+        "red" | "white" => Ok(Color::Red),
+        "black" => Ok(Color::Black),
+        other => Err(usage_error(format!("unknown color '{}', expected red or black", other))),
+    }
+}
+
+fn format_score(score: f32) -> String {
+    match score {
+        s if s == f32::INFINITY => "win".to_string(),
+        s if s == f32::NEG_INFINITY => "loss".to_string(),
+        s => format!("{:.2}", s),
+    }
+}
+
+// Moves of a line, each written on the board it is played from
+fn format_line(board: &Board, moves: &[mv::Move], notation: Notation) -> String {
+    let mut board = board.clone();
+    moves.iter()
+        .map(|m| {
+            let text = m.format(&board, notation);
+            let _ = board.make_move(m);
+            text
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// A few random legal moves from `board`, so repeated games differ
+fn random_opening(board: &Board, plies: u32, rng: &mut Rng) -> Board {
+    let mut board = board.clone();
+    for _ in 0..plies {
+        let moves = board.get_valid_moves();
+        if moves.is_empty() {
+            break;
         }
+        let _ = board.make_move(&moves[rng.below(moves.len())]);
     }
+    board
+}
 
-    // Check if game is over
-    if board.is_game_over() {
-        if let Some(winner) = board.get_winner() {
-            println!("{:?} wins!", winner);
-        } else {
-            println!("Draw!");
+// Read moves from stdin until a legal one is entered. None means quit.
+fn read_human_move(board: &Board, hint: &mut EnginePlayer, notation: Notation) -> Option<mv::Move> {
+    loop {
+        print!("Enter your move: ");
+        io::stdout().flush().unwrap();
+
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input).unwrap_or(0) == 0 {
+            return None;
+        }
+        let input = input.trim();
+
+        match input.to_lowercase().as_str() {
+            "q" => return None,
+            "hint" => {
+                if let Some(m) = hint.choose(board).best_move {
+                    println!("Hint: {}", m.format(board, notation));
+                }
+                continue;
+            },
+            _ => {},
+        }
+
+        match mv::Move::parse(input, board) {
+            Some(m) => return Some(m),
+            None => {
+                let legal: Vec<String> = board.get_valid_moves().iter().map(|m| m.format(board, notation)).collect();
+                println!("Illegal move '{}'. Legal moves: {}", input, legal.join(" "));
+            },
+        }
+    }
+}
+
+// Human vs human, human vs engine or engine vs engine
+// Players for Red and Black from --red/--black, or from --engine and
+// --color as a shorthand for a human against one engine
+fn sides(m: &Matches) -> Result<(PlayerKind, PlayerKind), CliError> {
+    let (mut red_kind, mut black_kind) = (PlayerKind::Human, PlayerKind::Human);
+    if let Some(name) = m.value("engine") {
+        let kind = player_kind(name, false)?;
+        match color(m.value("color").unwrap_or("red"))? {
+            Color::Red => black_kind = kind,
+            Color::Black => red_kind = kind,
         }
-        return false;
     }
+    if let Some(name) = m.value("red") {
+        red_kind = player_kind(name, true)?;
+    }
+    if let Some(name) = m.value("black") {
+        black_kind = player_kind(name, true)?;
+    }
+    Ok((red_kind, black_kind))
+}
 
-    true
+fn run_tui(m: &Matches) -> Result<(), CliError> {
+    let (red, black) = sides(m)?;
+    let config = TuiConfig {
+        red,
+        black,
+        limits: limits(m)?,
+        hash_mb: m.parse_or("hash", 64)?,
+        start: start_board(m)?,
+        notation: notation(m)?,
+        ponder: m.is_set("ponder"),
+    };
+    tui::run(config).map_err(|e| CliError::Failed(format!("terminal error: {}", e)))
 }
 
-fn main() {
-    println!("American Checkers");
-    println!("Red (r/R) vs Black (b/B) □ Are real squares and ■ are not");
+fn run_play(m: &Matches) -> Result<(), CliError> {
+    let (red_kind, black_kind) = sides(m)?;
+    let limits = limits(m)?;
+    let hash_mb = m.parse_or("hash", 64)?;
+    let notation = notation(m)?;
+    let max_plies: u32 = m.parse_or("max-plies", 300)?;
+    let ponder = m.is_set("ponder");
+    let mut red = EnginePlayer::new(red_kind, limits.clone(), hash_mb).with_evaluator(evaluator(m, "")?).with_ponder(ponder);
+    let mut black = EnginePlayer::new(black_kind, limits, hash_mb).with_evaluator(evaluator(m, "")?).with_ponder(ponder);
+    let mut board = start_board(m)?;
+
+    // A lone human playing Black sees the board from their side
+    let mut view = renderer(m)?;
+    if black_kind == PlayerKind::Human && red_kind != PlayerKind::Human {
+        view = view.facing(Color::Black);
+    }
+
+    println!("{}", board.variant.title());
+    println!("{}", render::legend(view.glyphs));
     println!("How to enter moves:");
     println!("1. Regular move:    'E3-F4'    (move one square diagonally)");
     println!("2. Capture move:    'B3-D5'    (jump over an opponent's piece)");
     println!("3. Multi-capture:   'B3-D5-B7' (multiple jumps in one turn)");
-    println!("Type 'q' to quit the game");
-
-    // Set this to true to run simulation, false for interactive mode
-    let simulation_mode = true;
-
-    // Predetermined moves for simulation
-    let simulation_moves = [// TODO:This simulaited moves still is not correct as Captures Must Be Prioritzed 
-        "E6-F5",
-        "H3-G4",
-        "F7-E6",
-        "D3-C4",
-        "E6-D5",
-        "B3-A4",
-        "A6-B5",
-        "F3-E4",
-        "G8-F7",
-        "G4-E6-G8",
-        "D5-B3",
-        "E2-D3",
-        "B5-C4",
-        "D1-E2",
-        "B3-D1",
-        "G8-F7",
-        "D1-F3-D5",
-        "F7-H5"
-    ];
-
-    let mut board = Board::new();
-
-    if simulation_mode {
-        println!("\nRunning simulation with {} predetermined moves...", simulation_moves.len());
-
-        for (i, &mv) in simulation_moves.iter().enumerate() {
-            println!("\n--- Move {} ({:?}'s turn): {} ---", i + 1, board.turn, mv);
-            board.display();
-
-            if !process_move(&mut board, mv) {
-                println!("Simulation ended early.");
-                break;
+    println!("Numeric moves like '11-15' or '15x24' work too");
+    println!("Type 'hint' for a suggestion or 'q' to quit the game");
+    println!("\nRed: {}, Black: {}", red_kind.name(), black_kind.name());
+
+    for _ in 0..max_plies {
+        print!("{}", view.render(&board));
+
+        if let Some(winner) = board.winner() {
+            println!("{:?} wins!", winner);
+            return Ok(());
+        }
+
+        let player = match board.turn {
+            Color::Red => &mut red,
+            Color::Black => &mut black,
+        };
+
+        let m = if player.kind == PlayerKind::Human {
+            match read_human_move(&board, player, notation) {
+                Some(m) => m,
+                None => {
+                    println!("Thanks for playing!");
+                    return Ok(());
+                }
+            }
+        } else {
+            let hits = player.ponder_hits;
+            let result = player.choose(&board);
+            let m = result.best_move.ok_or_else(|| format!("{:?} ({}) found no move", board.turn, player.kind.name()))?;
+            println!("{:?} ({}) plays {}  score {}  depth {}  {:.2}s{}",
+                     board.turn, player.kind.name(), m.format(&board, notation),
+                     format_score(result.score), result.depth, result.elapsed.as_secs_f64(),
+                     if player.ponder_hits > hits { "  (ponder hit)" } else { "" });
+            m
+        };
+
+        board.make_move(&m)?;
+        view.last_move = Some(m);
+    }
+
+    print!("{}", view.render(&board));
+    println!("Draw after {} plies", max_plies);
+    Ok(())
+}
+
+// Replay a script; an illegal move or a failed expectation is an error
+fn run_simulate(m: &Matches) -> Result<(), CliError> {
+    let path = m.positional.first()
+        .ok_or_else(|| usage_error("simulate needs a script file".to_string()))?;
+    let script = Script::load(path)?;
+    let notation = notation(m)?;
+    let (boards, quiet) = (m.is_set("boards"), m.is_set("quiet"));
+    let mut view = renderer(m)?;
+
+    if !quiet {
+        println!("Running {} ({} moves, {} expectations)", path, script.moves.len(), script.expectations.len());
+    }
+
+    let mut previous = script.start.clone();
+    let result = script.run(|ply, board, played| {
+        if let (Some(played), false) = (played, quiet) {
+            println!("{:>3}. {:?}: {}", ply, previous.turn, played.format(&previous, notation));
+            view.last_move = Some(played.clone());
+            if boards {
+                print!("{}", view.render(board));
+            }
+        }
+        previous = board.clone();
+    });
+
+    match result {
+        Ok(board) => {
+            if !quiet && !boards {
+                println!("\nFinal position:");
+                print!("{}", view.render(&board));
             }
+            println!("{}: all {} moves played, {} expectations met", path, script.moves.len(), script.expectations.len());
+            Ok(())
+        },
+        Err(e) => Err(CliError::Failed(format!("{}: {}", path, e))),
+    }
+}
+
+fn print_analysis(board: &Board, result: &SearchResult, notation: Notation) {
+    let seconds = result.elapsed.as_secs_f64();
+    let nps = if seconds > 0.0 { (result.nodes as f64 / seconds) as u64 } else { 0 };
+    println!("depth {:>2}  score {:>6}  nodes {:>9}  time {:.3}  nps {:>8}  pv {}",
+             result.depth, format_score(result.score), result.nodes, seconds, nps,
+             format_line(board, &result.pv, notation));
+}
+
+fn run_analyze(m: &Matches) -> Result<(), CliError> {
+    let notation = notation(m)?;
+    let mut board = start_board(m)?;
+    for text in m.value("moves").unwrap_or("").split_whitespace() {
+        let played = mv::Move::parse(text, &board).ok_or_else(|| format!("illegal move '{}'", text))?;
+        board.make_move(&played)?;
+    }
+
+    let mut engine = engine(m, "engine", "", PlayerKind::AlphaBeta)?;
+    println!("Position: {}", board.to_fen());
+    let result = engine.search(&board, |result| print_analysis(&board, result, notation));
+    if result.depth == 0 {
+        // A forced move is played without searching
+        print_analysis(&board, &result, notation);
+    }
+
+    match result.best_move {
+        Some(best) => println!("Best move: {}", best.format(&board, notation)),
+        None => match board.winner() {
+            Some(winner) => println!("No legal moves: {:?} has won", winner),
+            None => println!("No legal moves"),
+        },
+    }
+    Ok(())
+}
+
+// Run the perft suites up to --depth and fail on the first wrong count
+fn run_perft_suite(m: &Matches) -> Result<(), CliError> {
+    let max_depth: usize = m.parse_or("depth", 6)?;
+    let variants = match m.value("variant") {
+        Some(_) => vec![start_board(m)?.variant],
+        None => Variant::ALL.to_vec(),
+    };
+
+    let mut checked = 0;
+    for variant in variants {
+        for case in perft::suite(variant) {
+            let board = Board::from_variant_fen(variant, case.fen)?;
+            for (depth, &expected) in case.counts.iter().enumerate().take(max_depth) {
+                let count = perft::perft(&board, depth as u32 + 1);
+                if count != expected {
+                    return Err(CliError::Failed(format!("{} {}: perft({}) = {}, expected {}",
+                                                        variant, case.fen, depth + 1, count, expected)));
+                }
+                checked += 1;
+            }
+            println!("{:<13} {:<50} ok to depth {}", variant.name(), case.fen, case.counts.len().min(max_depth));
+        }
+    }
+    println!("All {} counts match", checked);
+    Ok(())
+}
 
+// Heap allocations so far, when built with the count-allocs feature
+fn allocation_count() -> Option<u64> {
+    #[cfg(feature = "count-allocs")]
+    return Some(allocations::count());
+    #[cfg(not(feature = "count-allocs"))]
+    None
+}
+
+fn run_perft(m: &Matches) -> Result<(), CliError> {
+    if m.is_set("suite") {
+        return run_perft_suite(m);
+    }
+    let board = start_board(m)?;
+    let depth: u32 = m.parse_or("depth", 6)?;
+    let notation = notation(m)?;
+
+    if m.is_set("divide") {
+        let mut total = 0;
+        for (root, count) in perft::divide(&board, depth) {
+            println!("{}: {}", root.format(&board, notation), count);
+            total += count;
+        }
+        println!("\nTotal: {}", total);
+        return Ok(());
+    }
+
+    for d in 1..=depth {
+        let (start, allocated) = (Instant::now(), allocation_count());
+        let count = perft::perft(&board, d);
+        print!("perft({}) = {:>12}  ({:.3}s", d, count, start.elapsed().as_secs_f64());
+        match (allocated, allocation_count()) {
+            (Some(before), Some(after)) => println!(", {} allocations)", after - before),
+            _ => println!(")"),
+        }
+    }
+    Ok(())
+}
 
+fn run_match(m: &Matches) -> Result<(), CliError> {
+    let mut first = engine(m, "engine", "", PlayerKind::AlphaBeta)?;
+    let mut second = engine(m, "opponent", "opponent-", PlayerKind::Mcts)?;
+    let games: u32 = m.parse_or("games", 10)?;
+    let random_plies = m.parse_or("random-plies", 4)?;
+    let max_plies = m.parse_or("max-plies", 300)?;
+    let mut rng = Rng::new(m.parse_or("seed", 1)?);
+    let start = start_board(m)?;
+
+    let (first_name, second_name) = (first.kind.name(), second.kind.name());
+    let (mut wins, mut draws, mut losses) = (0, 0, 0);
+    let mut opening = start.clone();
+
+    for game in 0..games {
+        // Each opening is played twice, once with either engine as Red
+        if game % 2 == 0 {
+            opening = random_opening(&start, random_plies, &mut rng);
         }
+        let first_color = if game % 2 == 0 { Color::Red } else { Color::Black };
+        first.new_game();
+        second.new_game();
+
+        let (red, black) = match first_color {
+            Color::Red => (&mut first, &mut second),
+            Color::Black => (&mut second, &mut first),
+        };
+        let record = player::play_engines(&opening, red, black, max_plies, |_, _, _| {});
+
+        let outcome = match record.result {
+            GameResult::Win(winner) if winner == first_color => { wins += 1; "win" },
+            GameResult::Win(_) => { losses += 1; "loss" },
+            GameResult::Draw => { draws += 1; "draw" },
+        };
+        println!("Game {}: {} as {:?}, {} in {} plies", game + 1, first_name, first_color, outcome, record.moves.len());
+    }
 
-        println!("\nFinal board state after simulation:");
-        board.display();
-    } else {
-        // Interactive game loop
-        loop {
-            // Display the current board state
-            board.display();
+    let score = (wins as f32 + draws as f32 * 0.5) / games.max(1) as f32;
+    println!("\n{} vs {}: +{} ={} -{}  ({:.1}%)", first_name, second_name, wins, draws, losses, score * 100.0);
+    if m.is_set("ponder") {
+        println!("Ponder hits: {} {}/{}, {} {}/{}",
+                 first_name, first.ponder_hits, first.ponder_hits + first.ponder_misses,
+                 second_name, second.ponder_hits, second.ponder_hits + second.ponder_misses);
+    }
+    Ok(())
+}
 
-            println!("{:?}'s turn", board.turn);
+// Positions searched by bench: the start, three positions from one opening
+// line and an ending with kings
+const BENCH_POSITIONS: [&str; 5] = [
+    "W:W21-32:B1-12",
+    "W:W21,23,24,25,26,27,28,29,30,31,32:B1,2,3,4,5,6,7,9,10,12,15",
+    "W:W19,21,22,23,26,27,29,30,31,32:B1,2,3,5,6,7,8,10,12,14",
+    "W:W17,21,22,23,26,27,29,30,31,32:B1,2,3,4,5,6,7,8,10,12,14",
+    "W:WK14,K19,27,30:BK3,K5,12,16",
+];
 
-            // Get the player's move
-            print!("Enter your move: ");
-            io::stdout().flush().unwrap();
+fn run_bench(m: &Matches) -> Result<(), CliError> {
+    let depth = m.parse_or("depth", 8)?;
+    let hash_mb = m.parse_or("hash", 64)?;
+    let evaluator = evaluator(m, "")?;
+    let mut total_nodes = 0;
+    let start = Instant::now();
 
-            let mut input = String::new();
-            io::stdin().read_line(&mut input).unwrap();
-            let input = input.trim();
+    for fen in BENCH_POSITIONS {
+        let board = Board::from_fen(fen)?;
+        let mut engine = AlphaBetaEngine::new(hash_mb);
+        let result = engine.search(&board, &SearchLimits::depth(depth), evaluator.as_ref(), |_| {});
+        println!("{:<72} {:>10} nodes  {:.3}s", fen, result.nodes, result.elapsed.as_secs_f64());
+        total_nodes += result.nodes;
+    }
+
+    let seconds = start.elapsed().as_secs_f64();
+    println!("\nTotal: {} nodes in {:.3}s, {} nps", total_nodes, seconds, (total_nodes as f64 / seconds.max(1e-9)) as u64);
+    Ok(())
+}
 
-            if !process_move(&mut board, input) {
-                break;
+fn run_selfplay(m: &Matches) -> Result<(), CliError> {
+    let mut red = engine(m, "engine", "", PlayerKind::AlphaBeta)?;
+    let mut black = engine(m, "engine", "", PlayerKind::AlphaBeta)?;
+    let games: u32 = m.parse_or("games", 1)?;
+    let random_plies = m.parse_or("random-plies", 0)?;
+    let max_plies = m.parse_or("max-plies", 300)?;
+    let notation = notation(m)?;
+    let mut rng = Rng::new(m.parse_or("seed", 1)?);
+    let start = start_board(m)?;
+
+    for game in 0..games {
+        red.new_game();
+        black.new_game();
+        let opening = random_opening(&start, random_plies, &mut rng);
+        let record = player::play_engines(&opening, &mut red, &mut black, max_plies, |_, _, _| {});
+
+        let result = match record.result {
+            GameResult::Win(Color::Red) => "1-0",
+            GameResult::Win(Color::Black) => "0-1",
+            GameResult::Draw => "1/2-1/2",
+        };
+        // PDN, for tune to learn from (and simulate to replay, one game at a time)
+        println!("[Event \"selfplay\"]");
+        println!("[Round \"{}\"]", game + 1);
+        if let Some(game_type) = opening.variant.pdn_game_type() {
+            println!("[GameType \"{}\"]", game_type);
+        }
+        println!("[FEN \"{}\"]", opening.to_fen());
+        println!("[Result \"{}\"]\n", result);
+        println!("{} {}\n", format_line(&record.start, &record.moves, notation), result);
+    }
+    Ok(())
+}
+
+fn run_protocol(_m: &Matches) -> Result<(), CliError> {
+    checkers_bot::protocol::run();
+    Ok(())
+}
+
+// Play one game against another engine over DXP
+fn run_dxp(m: &Matches) -> Result<(), CliError> {
+    let mut config = DxpConfig { variant: start_board(m)?.variant, ..DxpConfig::default() };
+    if let Some(host) = m.value("host") {
+        config.host = host.to_string();
+    }
+    config.minutes = m.parse_or("minutes", config.minutes)?;
+    config.moves = m.parse_or("moves", config.moves)?;
+    if let Some(name) = m.value("color") {
+        config.color = color(name)?;
+    }
+
+    let port = m.parse_or("port", dxp::DEFAULT_PORT)?;
+    let movetime_ms = m.parse_or("time", config.time_per_move().as_millis() as u64)?;
+    let limits = SearchLimits::movetime(Duration::from_millis(movetime_ms));
+    let mut engine = EnginePlayer::new(PlayerKind::AlphaBeta, limits, m.parse_or("hash", 16)?)
+        .with_ponder(m.is_set("ponder"));
+    let choose = |board: &Board| engine.choose(board).best_move;
+
+    let outcome = match (m.positional.first().map(String::as_str), m.positional.get(1)) {
+        (Some("listen"), _) => dxp::listen(port, &config, choose, |event| println!("{}", event))?,
+        (Some("connect"), Some(address)) => dxp::connect(address.as_str(), &config, choose, |event| println!("{}", event))?,
+        _ => return Err(usage_error("expected 'listen' or 'connect HOST:PORT'".to_string())),
+    };
+
+    println!("Game over: {:?}", outcome);
+    Ok(())
+}
+
+// Serve the browser board and its JSON API until killed
+fn run_serve(m: &Matches) -> Result<(), CliError> {
+    let defaults = ServeConfig::default();
+    let limits = match (m.value("depth"), m.value("time")) {
+        (None, None) => defaults.limits.clone(),
+        _ => limits(m)?,
+    };
+    let config = ServeConfig {
+        host: m.value("host").unwrap_or(&defaults.host).to_string(),
+        port: m.parse_or("port", defaults.port)?,
+        engine: match m.value("engine") {
+            Some(name) => player_kind(name, false)?,
+            None => defaults.engine,
+        },
+        limits,
+        hash_mb: m.parse_or("hash", defaults.hash_mb)?,
+        notation: notation(m)?,
+        start: start_board(m)?,
+    };
+    server::run(config).map_err(CliError::Failed)
+}
+
+// Fit the eval bar weights to a labelled dataset and write them to a file
+fn run_tune(m: &Matches) -> Result<(), CliError> {
+    let (Some(positions), Some(output)) = (m.positional.first(), m.positional.get(1)) else {
+        return Err(usage_error("tune needs a positions file and an output file".to_string()));
+    };
+
+    let variant = start_board(m)?.variant;
+    let positions = tune::load_positions(positions, variant).map_err(|e| format!("Error loading positions: {}", e))?;
+    let initial = match m.positional.get(2) {
+        Some(path) => EvalWeights::load(path).map_err(|e| format!("Error loading weights: {}", e))?,
+        None => EvalWeights::default(),
+    };
+
+    let tuned = tune::tune(&positions, &initial, &TuneConfig::default(), |p| match p.iteration {
+        0 => println!("Tuning {} positions, K = {:.2}, initial error = {:.6}", p.positions, p.k, p.error),
+        i => println!("Iteration {}: error = {:.6}", i, p.error),
+    });
+    tuned.save(output).map_err(|e| format!("Error writing weights: {}", e))?;
+    println!("Tuned weights written to {}:\n{}", output, tuned);
+    Ok(())
+}
+
+// Self-play training data generation
+fn run_datagen(m: &Matches) -> Result<(), CliError> {
+    let defaults = DatagenConfig::default();
+    let format = match m.value("format") {
+        Some(name) => DataFormat::from_name(name)
+            .ok_or_else(|| usage_error(format!("unknown format '{}', expected csv or bin", name)))?,
+        None => defaults.format,
+    };
+
+    let config = DatagenConfig {
+        games: m.parse_or("games", defaults.games)?,
+        nodes: m.parse_or("nodes", defaults.nodes)?,
+        random_plies: m.parse_or("random-plies", defaults.random_plies)?,
+        max_plies: m.parse_or("max-plies", defaults.max_plies)?,
+        seed: m.parse_or("seed", defaults.seed)?,
+        output: m.value("out").map(str::to_string).unwrap_or(defaults.output),
+        format,
+        resume: m.is_set("resume"),
+    };
+
+    let weights = match m.value("weights") {
+        Some(path) => EvalWeights::load(path).map_err(|e| format!("Error loading weights: {}", e))?,
+        None => EvalWeights::default(),
+    };
+
+    let stats = datagen::run(&config, &Heuristic::new(weights), |stats| match stats.games {
+        0 => println!("Generating games {}..{} into {} ({} positions already known)",
+                      stats.resumed + 1, config.games, config.output, stats.known),
+        played => println!("{}/{} games, {} positions written, {} duplicates skipped",
+                           stats.resumed + played, config.games, stats.written, stats.duplicates),
+    })?;
+    println!("Done: {} games, {} positions written", stats.games, stats.written);
+    Ok(())
+}
+
+fn run(args: &[String]) -> Result<(), CliError> {
+    // No command, or flags straight away, means play
+    let (name, rest) = match args.first().map(String::as_str) {
+        None => ("play", args),
+        Some(first) if first.starts_with("--") && first != "--help" => ("play", args),
+        Some(first) => (first, &args[1..]),
+    };
+
+    if matches!(name, "help" | "--help" | "-h") {
+        match rest.first().and_then(|n| COMMANDS.iter().find(|c| c.name == n)) {
+            Some(command) => print!("{}", command.help()),
+            None => print!("{}", cli::help(COMMANDS)),
+        }
+        return Ok(());
+    }
+
+    let command = COMMANDS.iter()
+        .find(|c| c.name == name)
+        .ok_or_else(|| usage_error(format!("unknown command '{}'", name)))?;
+    let matches = command.parse(rest).map_err(|e| match e {
+        CliError::Usage(message) => CliError::Usage(format!("{}\n{}", message, command.usage())),
+        other => other,
+    })?;
+
+    if matches.help {
+        print!("{}", command.help());
+        return Ok(());
+    }
+    set_threads(&matches)?;
+
+    match command.name {
+        "play" => run_play(&matches),
+        "simulate" => run_simulate(&matches),
+        "tui" => run_tui(&matches),
+        "analyze" => run_analyze(&matches),
+        "perft" => run_perft(&matches),
+        "match" => run_match(&matches),
+        "bench" => run_bench(&matches),
+        "selfplay" => run_selfplay(&matches),
+        "protocol" => run_protocol(&matches),
+        "dxp" => run_dxp(&matches),
+        "serve" => run_serve(&matches),
+        "tune" => run_tune(&matches),
+        "datagen" => run_datagen(&matches),
+        _ => unreachable!("every command has a handler"),
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            if matches!(e, CliError::Usage(_)) {
+                eprintln!("Run 'checkersBot help' for usage.");
             }
+            ExitCode::from(e.exit_code())
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,448 @@
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use crate::board::Board;
+use crate::evaluator::{Evaluator, Score};
+use crate::mv::Move;
+use crate::rng::Rng;
+use crate::search::{SearchLimits, SearchResult};
+
+// How a playout is finished once it leaves the tree
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rollout {
+    Random,  // Uniformly random moves
+    Guided,  // Mostly the move the evaluator likes best after one ply
+}
+
+#[derive(Debug, Clone)]
+pub struct MctsConfig {
+    pub exploration: f32,    // UCT constant c
+    pub rollout: Rollout,
+    pub rollout_depth: u32,  // Plies before a playout is cut off and evaluated
+    pub threads: usize,      // Independent trees searched in parallel
+    pub seed: u64,
+}
+
+impl Default for MctsConfig {
+    fn default() -> Self {
+        MctsConfig {
+            exploration: std::f32::consts::SQRT_2,
+            rollout: Rollout::Random,
+            rollout_depth: 40,
+            threads: rayon::current_num_threads().max(1),
+            seed: 1,
+        }
+    }
+}
+
+// Playouts per search when the limits give neither nodes nor time
+pub const DEFAULT_PLAYOUTS: u64 = 20_000;
+
+// How often a long search reports its current best line
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+// Scale used to turn evaluator scores into win probabilities and back
+const SCORE_SCALE: f32 = 0.5;
+
+struct Node {
+    board: Board,
+    mv: Option<Move>,         // Move that led here from the parent
+    parent: Option<usize>,
+    children: Vec<usize>,
+    untried: Vec<Move>,
+    visits: u32,
+    value: f32,               // Sum of rewards for the side that played mv
+}
+
+impl Node {
+    fn new(board: Board, mv: Option<Move>, parent: Option<usize>) -> Self {
+        let untried = if board.is_game_over() { Vec::new() } else { board.get_valid_moves() };
+        Node {
+            board,
+            mv,
+            parent,
+            children: Vec::new(),
+            untried,
+            visits: 0,
+            value: 0.0,
+        }
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.children.is_empty() && self.untried.is_empty()
+    }
+}
+
+// One search tree, stored as an arena with the root at index 0
+struct Tree {
+    nodes: Vec<Node>,
+    rng: Rng,
+}
+
+fn win_probability(score: Score) -> f32 {
+    1.0 / (1.0 + (-SCORE_SCALE * score).exp())
+}
+
+impl Tree {
+    fn new(board: &Board, rng: Rng) -> Self {
+        Tree {
+            nodes: vec![Node::new(board.clone(), None, None)],
+            rng,
+        }
+    }
+
+    // Keep only the subtree below `index`, which becomes the new root
+    fn reroot(&mut self, index: usize) {
+        let mut old: Vec<Option<Node>> = self.nodes.drain(..).map(Some).collect();
+        let mut remap: HashMap<usize, usize> = HashMap::new();
+        let mut queue = vec![index];
+
+        while let Some(i) = queue.pop() {
+            if let Some(node) = old[i].take() {
+                remap.insert(i, self.nodes.len());
+                queue.extend(node.children.iter().copied());
+                self.nodes.push(node);
+            }
+        }
+
+        for node in self.nodes.iter_mut() {
+            node.parent = node.parent.and_then(|p| remap.get(&p).copied());
+            node.children = node.children.iter().filter_map(|c| remap.get(c).copied()).collect();
+        }
+        self.nodes[0].parent = None;
+        self.nodes[0].mv = None;
+    }
+
+    // Reuse the part of the tree that is still relevant: the root itself, or
+    // a node up to two plies below it (our move and the opponent's reply)
+    fn reuse(&mut self, board: &Board) -> bool {
+        let hash = board.hash();
+        let root_children = self.nodes[0].children.clone();
+
+        if self.nodes[0].board.hash() == hash {
+            return true;
+        }
+
+        let found = root_children.iter()
+            .flat_map(|&c| std::iter::once(c).chain(self.nodes[c].children.clone()))
+            .find(|&i| self.nodes[i].board.hash() == hash);
+
+        match found {
+            Some(i) => {
+                self.reroot(i);
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn uct_child(&self, index: usize, exploration: f32) -> usize {
+        let parent_visits = (self.nodes[index].visits.max(1) as f32).ln();
+
+        *self.nodes[index].children.iter()
+            .max_by(|&&a, &&b| {
+                let uct = |i: usize| {
+                    let node = &self.nodes[i];
+                    let visits = node.visits.max(1) as f32;
+                    node.value / visits + exploration * (parent_visits / visits).sqrt()
+                };
+                uct(a).partial_cmp(&uct(b)).unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .expect("uct_child called on a node without children")
+    }
+
+    // Finish the game from `board` and return the reward for the side to move
+    fn rollout<E: Evaluator + ?Sized>(&mut self, mut board: Board, config: &MctsConfig, evaluator: &E) -> f32 {
+        let start_turn = board.turn;
+        let mut moves = Vec::new();
+
+        for _ in 0..config.rollout_depth {
+            if board.is_game_over() {
+                break;
+            }
+
+            moves.clear();
+            board.generate_moves(&mut moves);
+            if moves.is_empty() {
+                // Blocked: the side to move loses (or wins, in giveaway)
+                return if board.winner_when_stuck(board.turn) == start_turn { 1.0 } else { 0.0 };
+            }
+
+            let index = match config.rollout {
+                Rollout::Guided if self.rng.below(10) < 8 => {
+                    (0..moves.len())
+                        .filter_map(|i| {
+                            let undo = board.make_move(&moves[i]).ok()?;
+                            let score = -evaluator.evaluate(&board);
+                            board.unmake_move(&moves[i], undo);
+                            Some((i, score))
+                        })
+                        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+                        .map(|(i, _)| i)
+                        .unwrap_or(0)
+                },
+                _ => self.rng.below(moves.len()),
+            };
+
+            let _ = board.make_move(&moves[index]);
+        }
+
+        let reward = match board.get_winner() {
+            Some(winner) if board.is_game_over() => if winner == board.turn { 1.0 } else { 0.0 },
+            _ => win_probability(evaluator.evaluate(&board)),
+        };
+
+        if board.turn == start_turn { reward } else { 1.0 - reward }
+    }
+
+    // One selection / expansion / simulation / backpropagation cycle
+    fn playout<E: Evaluator + ?Sized>(&mut self, config: &MctsConfig, evaluator: &E) {
+        // Selection
+        let mut index = 0;
+        while self.nodes[index].untried.is_empty() && !self.nodes[index].children.is_empty() {
+            index = self.uct_child(index, config.exploration);
+        }
+
+        // Expansion
+        if !self.nodes[index].untried.is_empty() {
+            let pick = self.rng.below(self.nodes[index].untried.len());
+            let mv = self.nodes[index].untried.swap_remove(pick);
+
+            let mut board = self.nodes[index].board.clone();
+            let _ = board.make_move(&mv);
+
+            let child = self.nodes.len();
+            self.nodes.push(Node::new(board, Some(mv), Some(index)));
+            self.nodes[index].children.push(child);
+            index = child;
+        }
+
+        // Simulation, reward for the side to move at the leaf
+        let reward = if self.nodes[index].is_terminal() {
+            // No moves or no pieces left: the side to move has lost, or won
+            // in giveaway
+            let board = &self.nodes[index].board;
+            if board.winner_when_stuck(board.turn) == board.turn { 1.0 } else { 0.0 }
+        } else {
+            let board = self.nodes[index].board.clone();
+            self.rollout(board, config, evaluator)
+        };
+
+        // Backpropagation: each node holds the reward of the side that moved
+        // into it, which is the opponent of the side to move there
+        let mut reward_for_mover = 1.0 - reward;
+        let mut current = Some(index);
+        while let Some(i) = current {
+            self.nodes[i].visits += 1;
+            self.nodes[i].value += reward_for_mover;
+            reward_for_mover = 1.0 - reward_for_mover;
+            current = self.nodes[i].parent;
+        }
+    }
+
+    // Most visited line from the root
+    fn principal_variation(&self) -> Vec<Move> {
+        let mut pv = Vec::new();
+        let mut index = 0;
+
+        while let Some(&best) = self.nodes[index].children.iter().max_by_key(|&&c| self.nodes[c].visits) {
+            match &self.nodes[best].mv {
+                Some(mv) => pv.push(mv.clone()),
+                None => break,
+            }
+            index = best;
+        }
+
+        pv
+    }
+}
+
+// Monte Carlo Tree Search engine using UCT. With more than one thread it
+// searches independent trees in parallel (root parallelization) and adds up
+// their root statistics. Trees are kept between searches and reused when the
+// next position is found in them.
+pub struct MctsEngine {
+    pub config: MctsConfig,
+    trees: Vec<Tree>,
+}
+
+impl MctsEngine {
+    pub fn new(config: MctsConfig) -> Self {
+        MctsEngine { config, trees: Vec::new() }
+    }
+
+    // Forget the stored trees, e.g. when a new game starts
+    pub fn clear(&mut self) {
+        self.trees.clear();
+    }
+
+    // Search under the given limits. Every REPORT_INTERVAL, and once more at
+    // the end, the merged result so far is passed to on_progress.
+    pub fn search<E, F>(&mut self, board: &Board, limits: &SearchLimits, evaluator: &E, mut on_progress: F) -> SearchResult
+    where
+        E: Evaluator + ?Sized,
+        F: FnMut(&SearchResult),
+    {
+        let start = Instant::now();
+        let threads = self.config.threads.max(1);
+
+        // Reuse what we can, start fresh trees for the rest
+        let mut trees: Vec<Tree> = std::mem::take(&mut self.trees)
+            .into_iter()
+            .filter_map(|mut tree| if tree.reuse(board) { Some(tree) } else { None })
+            .collect();
+        while trees.len() < threads {
+            let seed = self.config.seed ^ (trees.len() as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+            trees.push(Tree::new(board, Rng::new(seed)));
+        }
+        trees.truncate(threads);
+
+        let playouts = match limits.nodes {
+            Some(nodes) => nodes,
+            None if limits.movetime.is_some() || limits.infinite => u64::MAX,
+            None => DEFAULT_PLAYOUTS,
+        };
+        let per_tree = playouts.div_ceil(threads as u64);
+        let config = &self.config;
+        let mut counts = vec![0u64; threads];
+
+        // Playouts run in rounds that end at the next report
+        let mut report_at = start + REPORT_INTERVAL;
+        loop {
+            trees.par_iter_mut()
+                .zip(counts.par_iter_mut())
+                .for_each(|(tree, count)| {
+                    while *count < per_tree {
+                        if *count % 64 == 0 && (limits.expired(start) || Instant::now() >= report_at) {
+                            break;
+                        }
+                        tree.playout(config, evaluator);
+                        *count += 1;
+                    }
+                });
+
+            let result = Self::merge(&trees, board, counts.iter().sum(), start);
+            on_progress(&result);
+            if counts.iter().all(|&count| count >= per_tree) || limits.expired(start) {
+                self.trees = trees;
+                return result;
+            }
+            report_at += REPORT_INTERVAL;
+        }
+    }
+
+    // The result so far, from the root statistics of every tree
+    fn merge(trees: &[Tree], board: &Board, done: u64, start: Instant) -> SearchResult {
+        // Merge root statistics from every tree by move
+        let mut totals: Vec<(Move, u32, f32)> = Vec::new();
+        for tree in trees {
+            for &c in &tree.nodes[0].children {
+                let node = &tree.nodes[c];
+                let Some(mv) = &node.mv else { continue };
+                match totals.iter_mut().find(|(m, _, _)| m == mv) {
+                    Some(entry) => {
+                        entry.1 += node.visits;
+                        entry.2 += node.value;
+                    },
+                    None => totals.push((mv.clone(), node.visits, node.value)),
+                }
+            }
+        }
+
+        let best = totals.iter().max_by_key(|(_, visits, _)| *visits);
+        let best_move = best.map(|(mv, _, _)| mv.clone())
+            .or_else(|| board.get_valid_moves().into_iter().next());
+
+        // Report the win rate as a score on the evaluator's scale
+        let score = best
+            .map(|(_, visits, value)| {
+                let p = (value / (*visits).max(1) as f32).clamp(0.001, 0.999);
+                (p / (1.0 - p)).ln() / SCORE_SCALE
+            })
+            .unwrap_or(0.0);
+
+        // The PV comes from the tree that explored the chosen move most
+        let pv = best_move.as_ref()
+            .and_then(|mv| {
+                trees.iter()
+                    .map(|tree| tree.principal_variation())
+                    .filter(|pv| pv.first() == Some(mv))
+                    .max_by_key(|pv| pv.len())
+            })
+            .unwrap_or_else(|| best_move.clone().into_iter().collect());
+
+        SearchResult {
+            best_move,
+            score,
+            depth: pv.len() as u32,
+            nodes: done,
+            pv,
+            elapsed: start.elapsed(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::Heuristic;
+    use crate::variant::Variant;
+
+    fn engine() -> MctsEngine {
+        MctsEngine::new(MctsConfig { threads: 1, ..MctsConfig::default() })
+    }
+
+    #[test]
+    fn gives_the_last_piece_away_in_giveaway() {
+        // 22-18 forces 15x22 and leaves Red with nothing, which wins
+        let board = Board::from_variant_fen(Variant::Giveaway, "W:W22:B15").unwrap();
+        let limits = SearchLimits { nodes: Some(2_000), ..SearchLimits::default() };
+        let result = engine().search(&board, &limits, &Heuristic::default(), |_| {});
+
+        assert_eq!(result.best_move, Some(Move::new(21, 17)));
+        assert!(result.score > 0.0, "score {}", result.score);
+    }
+
+    #[test]
+    fn finds_the_move_that_blocks_the_last_man() {
+        // 12-8 leaves Black's man on 4 without a move; 11-8 would let it
+        // jump to 11 and 11-7 lets it run
+        let board = Board::from_fen("W:W11,12:B4").unwrap();
+        let limits = SearchLimits { nodes: Some(2_000), ..SearchLimits::default() };
+        let result = engine().search(&board, &limits, &Heuristic::default(), |_| {});
+
+        assert_eq!(result.best_move, Some(Move::new(11, 7)));
+        assert!(result.score > 2.0, "score {}", result.score);
+        assert_eq!(result.pv.first(), result.best_move.as_ref());
+    }
+
+    #[test]
+    fn keeps_the_subtree_of_the_position_reached() {
+        let mut engine = engine();
+        let start = Board::new();
+        engine.search(&start, &SearchLimits::nodes(3_000), &Heuristic::default(), |_| {});
+
+        // Our move and the opponent's reply, both in the tree by now
+        let mut next = start.clone();
+        for mv in &engine.trees[0].principal_variation()[..2] {
+            next.make_move(mv).unwrap();
+        }
+        let tree = &mut engine.trees[0];
+        let visits = tree.nodes.iter().find(|n| n.board.hash() == next.hash()).unwrap().visits;
+        assert!(tree.reuse(&next));
+
+        assert_eq!(tree.nodes[0].board.to_fen(), next.to_fen());
+        assert_eq!((tree.nodes[0].mv.clone(), tree.nodes[0].parent, tree.nodes[0].visits), (None, None, visits));
+        for (i, node) in tree.nodes.iter().enumerate() {
+            for &c in &node.children {
+                assert_eq!(tree.nodes[c].parent, Some(i));
+            }
+        }
+
+        // A position from elsewhere in the game starts a fresh tree
+        let unrelated = Board::from_fen("W:W11,12:B4").unwrap();
+        assert!(!engine.trees[0].reuse(&unrelated));
+        let result = engine.search(&unrelated, &SearchLimits::nodes(500), &Heuristic::default(), |_| {});
+        assert_eq!(result.best_move, Some(Move::new(11, 7)));
+    }
+}
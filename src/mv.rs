@@ -1,27 +1,195 @@
-use crate::board::{Board, Color};
+use std::fmt;
+use crate::board::{Board, Piece};
 use std::iter::Iterator;
 
+// How moves are written for the user
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Notation {
+    Numeric,    // "11-15", "15x24"
+    Algebraic,  // "E3-F4", "B3-D5-F3"
+}
+
+impl Notation {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "numeric" | "num" => Some(Notation::Numeric),
+            "algebraic" | "alg" => Some(Notation::Algebraic),
+            _ => None,
+        }
+    }
+}
+
+// Most pieces one move can take: every piece of a full international side
+pub const MAX_CAPTURES: usize = 20;
+
+// A short run of squares kept inline, so a Move is a plain value that never
+// touches the heap. Long enough for the path of a MAX_CAPTURES capture.
+#[derive(Clone, Copy, Default)]
+pub struct SquareList {
+    len: u8,
+    squares: [u8; MAX_CAPTURES + 1],
+}
+
+impl SquareList {
+    pub const fn new() -> Self {
+        SquareList { len: 0, squares: [0; MAX_CAPTURES + 1] }
+    }
+
+    // None if the squares don't fit
+    pub fn from_slice(squares: &[usize]) -> Option<Self> {
+        let mut list = SquareList::new();
+        for &square in squares {
+            if list.len() == MAX_CAPTURES + 1 || square > u8::MAX as usize {
+                return None;
+            }
+            list.push(square);
+        }
+        Some(list)
+    }
+
+    pub fn push(&mut self, square: usize) {
+        assert!(self.len() <= MAX_CAPTURES, "more than {} squares in a move", MAX_CAPTURES + 1);
+        self.squares[self.len()] = square as u8;
+        self.len += 1;
+    }
+
+    pub fn pop(&mut self) -> Option<usize> {
+        let last = self.last()?;
+        self.len -= 1;
+        Some(last)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, i: usize) -> Option<usize> {
+        self.as_bytes().get(i).map(|&square| square as usize)
+    }
+
+    pub fn first(&self) -> Option<usize> {
+        self.get(0)
+    }
+
+    pub fn last(&self) -> Option<usize> {
+        self.len().checked_sub(1).and_then(|i| self.get(i))
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = usize> + ExactSizeIterator + Clone + '_ {
+        self.as_bytes().iter().map(|&square| square as usize)
+    }
+
+    pub fn contains(&self, square: usize) -> bool {
+        self.iter().any(|s| s == square)
+    }
+
+    pub fn starts_with(&self, squares: &[usize]) -> bool {
+        squares.len() <= self.len() && self.iter().zip(squares).all(|(a, &b)| a == b)
+    }
+
+    pub fn to_vec(&self) -> Vec<usize> {
+        self.iter().collect()
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.squares[..self.len()]
+    }
+}
+
+impl PartialEq for SquareList {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+impl Eq for SquareList {}
+
+impl PartialEq<[usize]> for SquareList {
+    fn eq(&self, other: &[usize]) -> bool {
+        self.iter().eq(other.iter().copied())
+    }
+}
+
+impl fmt::Debug for SquareList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
 // Represents a move in the game
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(into = "MoveJson", try_from = "MoveJson"))]
 pub struct Move {
     pub from: usize,
     pub to: usize,
-    pub captures: Vec<usize>,// Can be multiple capture moves
-    pub path: Vec<usize>,    // Sequence of positions for multi-jumps (including from and to)
+    pub captures: SquareList, // Can be multiple capture moves
+    pub path: SquareList,     // Sequence of positions for multi-jumps (including from and to)
+}
+
+// Moves serialize with the standard square numbers, 1 and up, like FEN and
+// the web API: {"from": 22, "to": 15, "path": [22, 15], "captures": [18]}.
+// The path may be left out of a simple move or single capture.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct MoveJson {
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    path: Vec<usize>,
+    #[serde(default)]
+    captures: Vec<usize>,
+}
+
+#[cfg(feature = "serde")]
+impl From<Move> for MoveJson {
+    fn from(m: Move) -> Self {
+        let number = |squares: SquareList| squares.iter().map(|s| s + 1).collect();
+        MoveJson { from: m.from + 1, to: m.to + 1, path: number(m.path), captures: number(m.captures) }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<MoveJson> for Move {
+    type Error = String;
+
+    fn try_from(json: MoveJson) -> Result<Self, String> {
+        let index = |square: usize| square.checked_sub(1).ok_or_else(|| "squares are numbered from 1".to_string());
+        let indices = |squares: Vec<usize>| {
+            let squares = squares.into_iter().map(index).collect::<Result<Vec<usize>, String>>()?;
+            SquareList::from_slice(&squares).ok_or_else(|| format!("more than {} captures", MAX_CAPTURES))
+        };
+
+        let (from, to) = (index(json.from)?, index(json.to)?);
+        let path = match indices(json.path)? {
+            path if path.is_empty() => indices(vec![from + 1, to + 1])?,
+            path if path.first() == Some(from) && path.last() == Some(to) => path,
+            _ => return Err("path must run from 'from' to 'to'".to_string()),
+        };
+        Ok(Move { from, to, captures: indices(json.captures)?, path })
+    }
 }
 
 impl Move {
-    pub fn new(from: usize, to: usize, captures: Vec<usize>) -> Self {
+    // A step to a neighbouring (or, for a flying king, more distant) square
+    pub fn new(from: usize, to: usize) -> Self {
+        let mut path = SquareList::new();
+        path.push(from);
+        path.push(to);
         Move {
             from,
             to,
-            captures,
-            path: vec![from, to],
+            captures: SquareList::new(),
+            path,
         }
     }
 
     // Create a move with specified path for multi-jumps
-    pub fn with_path(from: usize, to: usize, captures: Vec<usize>, path: Vec<usize>) -> Self {
+    pub fn with_path(from: usize, to: usize, captures: SquareList, path: SquareList) -> Self {
         Move {
             from,
             to,
@@ -36,7 +204,7 @@ impl Move {
             // Functional approach for multi-jump notation
             self.path.iter()
                 .enumerate()
-                .map(|(i, &position)| {
+                .map(|(i, position)| {
                     let (row, col) = board.index_to_coords(position);
                     let notation = format!("{}{}", (col as u8 + b'A') as char, row + 1);
                     if i > 0 { format!("-{}", notation) } else { notation }
@@ -55,6 +223,49 @@ impl Move {
         }
     }
 
+    // Standard numeric notation on squares 1-32 (1-50): "11-15" for a step and
+    // "15x24" or "6x15x24" for captures, listing every landing square
+    pub fn to_numeric(&self) -> String {
+        let separator = if self.captures.is_empty() { "-" } else { "x" };
+        self.path.iter()
+            .map(|position| (position + 1).to_string())
+            .collect::<Vec<String>>()
+            .join(separator)
+    }
+
+    pub fn format(&self, board: &Board, notation: Notation) -> String {
+        match notation {
+            Notation::Numeric => self.to_numeric(),
+            Notation::Algebraic => self.to_notation(board),
+        }
+    }
+
+    // Find the legal move written in either notation. A numeric capture may
+    // give just its first and last square if that is unambiguous.
+    pub fn parse(text: &str, board: &Board) -> Option<Self> {
+        let text = text.trim();
+        let legal = board.get_valid_moves();
+
+        if text.chars().any(|c| c.is_ascii_alphabetic() && c != 'x') {
+            let parsed = Self::from_notation(text, board)?;
+            return legal.into_iter().find(|m| m.path == parsed.path);
+        }
+
+        let squares: Option<Vec<usize>> = text.split(['-', 'x'])
+            .map(|s| s.trim().parse::<usize>().ok().filter(|n| (1..=board.num_squares()).contains(n)).map(|n| n - 1))
+            .collect();
+        let squares = squares.filter(|s| s.len() >= 2)?;
+
+        let mut matches = legal.into_iter()
+            .filter(|m| m.from == squares[0] && m.to == squares[squares.len() - 1])
+            .filter(|m| squares.len() == 2 || m.path == squares[..]);
+
+        match (matches.next(), matches.next()) {
+            (Some(m), None) => Some(m),
+            _ => None,
+        }
+    }
+
     pub fn from_notation(pos: &str, board: &Board) -> Option<Self> {
         //println!("Parsing : '{}'", pos);
 
@@ -69,27 +280,27 @@ impl Move {
         // Convert all positions in the path to board indices using map
         let positions_result: Option<Vec<((usize, usize), usize)>> = parts.iter()
             .map(|&notation| {
-                // Validate and parse a single notation like "E3"
+                // Validate and parse a single notation like "E3" (or "J10")
                 if notation.len() < 2 {
                     //println!("Notation part too short: {}", notation);
                     return None;
                 }
 
-                let chars: Vec<char> = notation.chars().collect();
-                let col_char = chars[0];
-                let row_char = chars[1];
+                let col_char = notation.chars().next()?;
+                let row_text = &notation[col_char.len_utf8()..];
 
-                if !col_char.is_ascii_alphabetic() || !row_char.is_ascii_digit() {
+                if !col_char.is_ascii_alphabetic() || !row_text.chars().all(|c| c.is_ascii_digit()) {
                     //println!("Invalid format in position: {}", notation);
                     return None;
                 }
 
                 let col = (col_char.to_ascii_uppercase() as u8 - b'A') as usize;
-                let row = row_char.to_digit(10).map(|d| d as usize - 1)?;
+                let row = row_text.parse::<usize>().ok()?.checked_sub(1)?;
 
                 //println!("Position: ({}, {})", row, col);
 
-                if row >= 8 || col >= 8 {
+                let size = board.rules().size();
+                if row >= size || col >= size {
                     //println!("Position out of bounds: {}", notation);
                     return None;
                 }
@@ -102,10 +313,7 @@ impl Move {
             .collect();
 
         // Early return if any position parsing failed
-        let positions = match positions_result {
-            Some(p) => p,
-            None => return None,
-        };
+        let positions = positions_result?;
 
         // Extract indices for path
         let indices: Vec<usize> = positions.iter().map(|&(_, index)| index).collect();
@@ -115,7 +323,7 @@ impl Move {
         let to_index = indices[indices.len() - 1];
 
         // Calculate captures for multi-jumps
-        let captures = if indices.len() > 2 {
+        let captures: Vec<usize> = if indices.len() > 2 {
             // Process consecutive position pairs to find captures
             positions.windows(2)
                 .filter_map(|window| {
@@ -173,225 +381,29 @@ impl Move {
         Some(Move {
             from: from_index,
             to: to_index,
-            captures,
-            path: indices,
+            captures: SquareList::from_slice(&captures)?,
+            path: SquareList::from_slice(&indices)?,
         })
     }
 }
 
-// The definitive function to check if a move is valid according to checkers rules
+// The definitive function to check if a move is valid: it has to be one of
+// the moves the generator allows under the board's rules
 pub fn is_valid_move(board: &Board, m: &Move) -> bool {
-    //println!("Validating move: From {} to {}, Path: {:?}, Captures: {:?}", m.from, m.to, m.path, m.captures);
-
-    // Basic validation checks
-    if m.from >= 32 || m.to >= 32 {
-        //println!("Invalid indices");
-        return false;
-    }
-
-    let piece = board.squares[m.from];
-    //println!("Piece at source: '{}'", piece);
+    board.get_valid_moves().contains(m)
+}
 
-    // Check if piece belongs to current player using pattern matching
-    let piece_belongs_to_current_player = match (board.turn, piece) {
-        (Color::Red, 'r') | (Color::Red, 'R') => true,
-        (Color::Black, 'b') | (Color::Black, 'B') => true,
+// Whether a man of this kind crowns on the square: the far row from its side
+pub fn crowning_square(board: &Board, piece: Piece, index: usize) -> bool {
+    match (piece, board.index_to_coords(index)) {
+        (Piece::RedMan, (0, _)) => true,
+        (Piece::BlackMan, (row, _)) => row == board.rules().size() - 1,
         _ => false
-    };
-
-    if !piece_belongs_to_current_player {
-        //println!("Piece doesn't belong to current player");
-        return false;
-    }
-
-    // Check if destination is empty
-    if board.squares[m.to] != '□' {
-        //println!("Destination not empty");
-        return false;
-    }
-
-    let is_king = piece == 'R' || piece == 'B';
-
-    // For multi-jumps, validate each segment of the path
-    if m.path.len() > 2 {
-        //println!("Validating multi-jump path with {} segments", m.path.len() - 1);
-
-        // Each captured piece must belong to the opponent - use all() to check
-        let all_captures_valid = m.captures.iter().all(|&capture_index| {
-            let captured_piece = board.squares[capture_index];
-            //println!("Checking captured piece at index {}: '{}'", capture_index, captured_piece);
-
-            let is_valid_capture = match board.turn {
-                Color::Red => captured_piece == 'b' || captured_piece == 'B',
-                Color::Black => captured_piece == 'r' || captured_piece == 'R',
-            };
-
-            if !is_valid_capture {
-                //println!("Invalid capture: not an opponent's piece");
-            }
-
-            is_valid_capture
-        });
-
-        if !all_captures_valid {
-            return false;
-        }
-
-        // Check each segment of the path using windows() and all()
-        let all_segments_valid = m.path.windows(2).all(|window| {
-            let from_pos = window[0];
-            let to_pos = window[1];
-
-            let (from_row, from_col) = board.index_to_coords(from_pos);
-            let (to_row, to_col) = board.index_to_coords(to_pos);
-
-            let row_diff = (from_row as i32 - to_row as i32).abs();
-            let col_diff = (from_col as i32 - to_col as i32).abs();
-
-            //println!("Checking jump segment {} -> {}: row diff = {}, col diff = {}", from_pos, to_pos, row_diff, col_diff);
-
-            // Each jump must be diagonal
-            if row_diff != col_diff {
-                //println!("Jump segment not diagonal");
-                return false;
-            }
-
-            // Each jump must be 2 squares (capture)
-            if row_diff != 2 {
-                //println!("Jump segment distance not 2");
-                return false;
-            }
-
-            // For regular pieces, check direction
-            if !is_king {
-                match piece {
-                    'r' if from_row <= to_row => {
-                        //println!("Red pawn can only move/capture up");
-                        return false;
-                    },
-                    'b' if from_row >= to_row => {
-                        //println!("Black pawn can only move/capture down");
-                        return false;
-                    },
-                    _ => {}
-                }
-            }
-
-            true
-        });
-
-        if !all_segments_valid {
-            return false;
-        }
-
-        // The number of captures = jumps
-        if m.captures.len() != m.path.len() - 1 {
-            //println!("Capture count doesn't match jump count");
-            return false;
-        }
-
-        return true;
-    }
-
-    // Simple move (non-multi-jump)
-    let (from_row, from_col) = board.index_to_coords(m.from);
-    let (to_row, to_col) = board.index_to_coords(m.to);
-
-    //println!("From: ({}, {}), To: ({}, {})", from_row, from_col, to_row, to_col);
-
-    let row_diff = (from_row as i32 - to_row as i32).abs();
-    let col_diff = (from_col as i32 - to_col as i32).abs();
-
-    //println!("Row diff: {}, Col diff: {}", row_diff, col_diff);
-
-    // Check if move is diagonal
-    if row_diff != col_diff {
-        //println!("Not diagonal");
-        return false;
     }
-
-    // Regular move
-    if m.captures.is_empty() {
-        // Check if it's only one square
-        if row_diff != 1 {
-            //println!("Regular move must be one square");
-            return false;
-        }
-
-        // Direction check for pawns with pattern matching
-        if !is_king {
-            match (piece, from_row.cmp(&to_row)) {
-                ('r', std::cmp::Ordering::Less | std::cmp::Ordering::Equal) => {
-                    //println!("Red pawn can only move up");
-                    return false;
-                },
-                ('b', std::cmp::Ordering::Greater | std::cmp::Ordering::Equal) => {
-                    //println!("Black pawn can only move down");
-                    return false;
-                },
-                _ => {}
-            }
-        }
-    }
-    // Capture move (single jump)
-    else {
-        if row_diff != 2 {
-            //println!("Capture jump distance must be 2");
-            return false;
-        }
-
-        // Use all() to check all captures are valid
-        let all_captures_valid = m.captures.iter().all(|&capture_index| {
-            let captured_piece = board.squares[capture_index];
-            //println!("Validating capture at index {}, piece: '{}'", capture_index, captured_piece);
-
-            match (board.turn, captured_piece) {
-                (Color::Red, 'b') | (Color::Red, 'B') => true,
-                (Color::Black, 'r') | (Color::Black, 'R') => true,
-                _ => {
-                    //println!("Not a valid piece to capture");
-                    false
-                }
-            }
-        });
-
-        if !all_captures_valid {
-            return false;
-        }
-
-        // Direction check for pawns with pattern matching
-        if !is_king {
-            match (piece, from_row.cmp(&to_row)) {
-                ('r', std::cmp::Ordering::Less | std::cmp::Ordering::Equal) => {
-                    //println!("Red pawn can only capture up");
-                    return false;
-                },
-                ('b', std::cmp::Ordering::Greater | std::cmp::Ordering::Equal) => {
-                    //println!("Black pawn can only capture down");
-                    return false;
-                },
-                _ => {}
-            }
-        }
-    }
-
-
-    //println!("Move is valid");
-    true
 }
 
-// Function to determine if a piece should be promoted to a king
-pub fn promote(board: &Board, index: usize) -> bool {
-    // Use pattern matching to check for promotion conditions
-    match (board.squares[index], board.index_to_coords(index)) {
-        ('r', (0, _)) => {
-            //println!("Promoting red piece to king");
-            true
-        },
-        ('b', (7, _)) => {
-            //println!("Promoting black piece to king");
-            true
-        },
-        _ => false
-    }
-}
\ No newline at end of file
+// Function to determine if the piece that just played m (now standing on
+// m.to) should be promoted to a king, under the board's rules
+pub fn promote(board: &Board, m: &Move) -> bool {
+    board.rules().promote(board, m)
+}
@@ -0,0 +1,383 @@
+use std::fs;
+use crate::board::{Board, Color, Piece, Undo};
+use crate::evaluator::{Evaluator, Heuristic, Score};
+use crate::mv::Move;
+use crate::variant::Variant;
+
+// NNUE style evaluator: a sparse input layer over the 32 squares whose
+// output (the accumulator) can be updated incrementally as moves are made,
+// followed by two small dense layers.
+//
+// Each side has its own accumulator that sees the board from its point of
+// view (own/opponent pieces, squares mirrored for Black). The side to move's
+// accumulator goes first into the dense layers, so the output is already
+// relative to the side to move.
+//
+// Weights file layout (all numbers little endian):
+//   magic  b"CKNN", version u32 = 1
+//   hidden u32, hidden2 u32
+//   w1 [INPUTS * hidden] f32, feature major
+//   b1 [hidden] f32
+//   w2 [2 * hidden * hidden2] f32, input major
+//   b2 [hidden2] f32
+//   w3 [hidden2] f32
+//   b3 f32
+
+pub const INPUTS: usize = 4 * 32; // own man, own king, their man, their king
+const MAGIC: &[u8; 4] = b"CKNN";
+const VERSION: u32 = 1;
+
+#[derive(Debug, Clone)]
+pub struct Network {
+    pub hidden: usize,
+    pub hidden2: usize,
+    pub w1: Vec<f32>,
+    pub b1: Vec<f32>,
+    pub w2: Vec<f32>,
+    pub b2: Vec<f32>,
+    pub w3: Vec<f32>,
+    pub b3: f32,
+}
+
+// Input feature of a piece on a square, seen from one side's perspective
+fn feature(perspective: Color, piece: Piece, index: usize) -> Option<usize> {
+    let owner = piece.color()?;
+    let relative = if owner == perspective { 0 } else { 2 };
+    let kind = if piece.is_king() { 1 } else { 0 };
+
+    // Mirror the board for Black so both sides see themselves at the bottom
+    let square = match perspective {
+        Color::Red => index,
+        Color::Black => 31 - index,
+    };
+
+    Some((relative + kind) * 32 + square)
+}
+
+fn clipped_relu(x: f32) -> f32 {
+    x.clamp(0.0, 1.0)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, n: usize) -> Result<&[u8], String> {
+        let end = self.pos + n;
+        if end > self.bytes.len() {
+            return Err("weights file is truncated".to_string());
+        }
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn f32s(&mut self, n: usize) -> Result<Vec<f32>, String> {
+        let bytes = self.take(n * 4)?;
+        Ok(bytes.chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect())
+    }
+}
+
+impl Network {
+    // A network with every weight set to zero, mostly useful as a starting
+    // point for tools that fill the weights in themselves
+    pub fn zeroed(hidden: usize, hidden2: usize) -> Self {
+        Network {
+            hidden,
+            hidden2,
+            w1: vec![0.0; INPUTS * hidden],
+            b1: vec![0.0; hidden],
+            w2: vec![0.0; 2 * hidden * hidden2],
+            b2: vec![0.0; hidden2],
+            w3: vec![0.0; hidden2],
+            b3: 0.0,
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let mut reader = Reader { bytes, pos: 0 };
+
+        if reader.take(4)? != MAGIC {
+            return Err("not a network weights file".to_string());
+        }
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(format!("unsupported weights version {}", version));
+        }
+
+        let hidden = reader.u32()? as usize;
+        let hidden2 = reader.u32()? as usize;
+
+        let network = Network {
+            hidden,
+            hidden2,
+            w1: reader.f32s(INPUTS * hidden)?,
+            b1: reader.f32s(hidden)?,
+            w2: reader.f32s(2 * hidden * hidden2)?,
+            b2: reader.f32s(hidden2)?,
+            w3: reader.f32s(hidden2)?,
+            b3: reader.f32s(1)?[0],
+        };
+
+        if reader.pos != bytes.len() {
+            return Err("trailing data after weights".to_string());
+        }
+
+        Ok(network)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(self.hidden as u32).to_le_bytes());
+        bytes.extend_from_slice(&(self.hidden2 as u32).to_le_bytes());
+
+        [&self.w1, &self.b1, &self.w2, &self.b2, &self.w3, &vec![self.b3]]
+            .iter()
+            .flat_map(|values| values.iter())
+            .for_each(|v| bytes.extend_from_slice(&v.to_le_bytes()));
+
+        bytes
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let bytes = fs::read(path)
+            .map_err(|e| format!("could not read '{}': {}", path, e))?;
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_bytes())
+            .map_err(|e| format!("could not write '{}': {}", path, e))
+    }
+
+    // Run the dense layers on top of an accumulator. Each second layer unit
+    // is summed on its own, so nothing is allocated per evaluation.
+    pub fn forward(&self, acc: &Accumulator, turn: Color) -> Score {
+        let (own, their) = match turn {
+            Color::Red => (&acc.red, &acc.black),
+            Color::Black => (&acc.black, &acc.red),
+        };
+
+        let layer2 = |unit: usize| {
+            own.iter()
+                .chain(their.iter())
+                .map(|&x| clipped_relu(x))
+                .enumerate()
+                .filter(|&(_, x)| x != 0.0)
+                .fold(self.b2[unit], |out, (i, x)| out + self.w2[i * self.hidden2 + unit] * x)
+        };
+
+        self.b3 + (0..self.hidden2)
+            .map(|unit| clipped_relu(layer2(unit)) * self.w3[unit])
+            .sum::<f32>()
+    }
+
+    fn add_feature(&self, values: &mut [f32], feature: usize, sign: f32) {
+        let row = &self.w1[feature * self.hidden..(feature + 1) * self.hidden];
+        values.iter_mut().zip(row).for_each(|(v, w)| *v += sign * w);
+    }
+}
+
+// Output of the input layer for both perspectives
+#[derive(Debug, Clone)]
+pub struct Accumulator {
+    pub red: Vec<f32>,
+    pub black: Vec<f32>,
+}
+
+impl Accumulator {
+    // Compute the accumulator from scratch
+    pub fn new(network: &Network, board: &Board) -> Self {
+        let mut acc = Accumulator {
+            red: network.b1.clone(),
+            black: network.b1.clone(),
+        };
+
+        for i in 0..32 {
+            acc.toggle_piece(network, board.squares[i], i, 1.0);
+        }
+
+        acc
+    }
+
+    fn toggle_piece(&mut self, network: &Network, piece: Piece, index: usize, sign: f32) {
+        if let Some(f) = feature(Color::Red, piece, index) {
+            network.add_feature(&mut self.red, f, sign);
+        }
+        if let Some(f) = feature(Color::Black, piece, index) {
+            network.add_feature(&mut self.black, f, sign);
+        }
+    }
+
+    // Update the accumulator for m, called right after Board::make_move with
+    // the board it played m on and the Undo it returned. Only the squares the
+    // move touched (source, destination including a promotion, and captures)
+    // are added or removed.
+    pub fn make_move(&mut self, network: &Network, board: &Board, m: &Move, undo: &Undo) {
+        self.apply_move(network, board, m, undo, 1.0);
+    }
+
+    // Take m back out of the accumulator, called right before
+    // Board::unmake_move while the board still shows the move played
+    pub fn unmake_move(&mut self, network: &Network, board: &Board, m: &Move, undo: &Undo) {
+        self.apply_move(network, board, m, undo, -1.0);
+    }
+
+    fn apply_move(&mut self, network: &Network, board: &Board, m: &Move, undo: &Undo, sign: f32) {
+        self.toggle_piece(network, undo.moved(), m.from, -sign);
+        self.toggle_piece(network, board.squares[m.to], m.to, sign);
+        for (i, capture_index) in m.captures.iter().enumerate() {
+            self.toggle_piece(network, undo.captured(i), capture_index, -sign);
+        }
+    }
+}
+
+// One accumulator per ply of a search. A move copies the current one up a
+// slot and updates it incrementally, a takeback just drops back a slot, so
+// taking back is exact and free. Slots are kept once created, so nothing is
+// allocated after the stack has grown to the search's depth.
+#[derive(Debug, Clone)]
+pub struct AccumulatorStack<'a> {
+    network: &'a Network,
+    accumulators: Vec<Accumulator>,
+    len: usize,
+}
+
+impl<'a> AccumulatorStack<'a> {
+    pub fn new(network: &'a Network, board: &Board) -> Self {
+        AccumulatorStack {
+            network,
+            accumulators: vec![Accumulator::new(network, board)],
+            len: 1,
+        }
+    }
+
+    pub fn current(&self) -> &Accumulator {
+        &self.accumulators[self.len - 1]
+    }
+
+    // Called right after Board::make_move, like Accumulator::make_move
+    pub fn make_move(&mut self, board: &Board, m: &Move, undo: &Undo) {
+        if self.len == self.accumulators.len() {
+            self.accumulators.push(self.current().clone());
+        } else {
+            let (below, above) = self.accumulators.split_at_mut(self.len);
+            let (from, to) = (&below[self.len - 1], &mut above[0]);
+            to.red.copy_from_slice(&from.red);
+            to.black.copy_from_slice(&from.black);
+        }
+        self.accumulators[self.len].make_move(self.network, board, m, undo);
+        self.len += 1;
+    }
+
+    // Called next to Board::unmake_move for the last move made
+    pub fn unmake_move(&mut self) {
+        self.len = (self.len - 1).max(1);
+    }
+
+    // The network's score for the board the current accumulator belongs to
+    pub fn evaluate(&self, board: &Board) -> Score {
+        self.network.forward(self.current(), board.turn)
+    }
+}
+
+// Evaluator backed by a network. The alpha-beta search asks for the network
+// and keeps an AccumulatorStack next to its board; evaluate() itself gets a
+// plain board, so it refreshes the accumulator from scratch.
+#[derive(Debug, Clone)]
+pub struct NnueEvaluator {
+    pub network: Network,
+}
+
+impl NnueEvaluator {
+    pub fn new(network: Network) -> Self {
+        NnueEvaluator { network }
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        Network::load(path).map(Self::new)
+    }
+}
+
+impl Evaluator for NnueEvaluator {
+    fn network(&self, board: &Board) -> Option<&Network> {
+        (board.variant == Variant::American).then_some(&self.network)
+    }
+
+    fn evaluate(&self, board: &Board) -> Score {
+        // Networks are trained on the American board; other variants get
+        // the hand-written eval
+        if board.variant != Variant::American {
+            return Heuristic::default().evaluate(board);
+        }
+        let acc = Accumulator::new(&self.network, board);
+        self.network.forward(&acc, board.turn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    fn random_network(rng: &mut Rng) -> Network {
+        let mut network = Network::zeroed(16, 8);
+        for w in network.w1.iter_mut().chain(&mut network.b1).chain(&mut network.w2)
+            .chain(&mut network.b2).chain(&mut network.w3) {
+            *w = (rng.below(2001) as f32 - 1000.0) / 2000.0;
+        }
+        network
+    }
+
+    fn assert_close(incremental: &Accumulator, refreshed: &Accumulator) {
+        for (a, b) in incremental.red.iter().chain(&incremental.black).zip(refreshed.red.iter().chain(&refreshed.black)) {
+            assert!((a - b).abs() < 1e-4, "incremental {} vs refreshed {}", a, b);
+        }
+    }
+
+    #[test]
+    fn incremental_updates_match_a_refresh() {
+        let mut rng = Rng::new(29);
+        let evaluator = NnueEvaluator::new(random_network(&mut rng));
+        let network = &evaluator.network;
+        let mut board = Board::new();
+        let mut acc = Accumulator::new(network, &board);
+        let mut stack = AccumulatorStack::new(network, &board);
+        let mut played = Vec::new();
+
+        // Random games that now and then take back a few moves
+        for _ in 0..400 {
+            let moves = board.get_valid_moves();
+            if moves.is_empty() || rng.below(4) == 0 {
+                for _ in 0..rng.below(3) + 1 {
+                    let Some((m, undo)) = played.pop() else { break };
+                    acc.unmake_move(network, &board, &m, &undo);
+                    stack.unmake_move();
+                    board.unmake_move(&m, undo);
+                }
+            } else {
+                let m = moves[rng.below(moves.len())].clone();
+                let undo = board.make_move(&m).unwrap();
+                acc.make_move(network, &board, &m, &undo);
+                stack.make_move(&board, &m, &undo);
+                played.push((m, undo));
+            }
+
+            let refreshed = Accumulator::new(network, &board);
+            assert_close(&acc, &refreshed);
+            assert_close(stack.current(), &refreshed);
+            assert!((stack.evaluate(&board) - evaluator.evaluate(&board)).abs() < 1e-4);
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use rayon::prelude::*;
+use crate::board::Board;
+use crate::mv::Move;
+use crate::variant::Variant;
+
+// Number of leaf positions `depth` plies below the board. A multi-jump
+// counts as a single move, and a side without moves is a leaf of its own
+// with no children.
+pub fn perft(board: &Board, depth: u32) -> u64 {
+    if depth <= 1 {
+        return count(&mut board.clone(), depth, &mut vec![Vec::new(); 1]);
+    }
+
+    // The root moves are shared out between threads. Each worker makes and
+    // takes back moves on its own copy of the board, with one move list per
+    // ply that is reused at every node, so nothing is allocated per node.
+    board.get_valid_moves()
+        .par_iter()
+        .map_init(|| (board.clone(), vec![Vec::new(); depth as usize]), |(next, lists), m| {
+            let Ok(undo) = next.make_move(m) else { return 0 };
+            let nodes = count(next, depth - 1, lists);
+            next.unmake_move(m, undo);
+            nodes
+        })
+        .sum()
+}
+
+fn count(board: &mut Board, depth: u32, lists: &mut [Vec<Move>]) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    let [moves, rest @ ..] = lists else {
+        return 0;
+    };
+    moves.clear();
+    board.generate_moves(moves);
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+
+    moves.iter()
+        .map(|m| {
+            let Ok(undo) = board.make_move(m) else { return 0 };
+            let nodes = count(board, depth - 1, rest);
+            board.unmake_move(m, undo);
+            nodes
+        })
+        .sum()
+}
+
+// Perft split by root move, for finding where two generators disagree
+pub fn divide(board: &Board, depth: u32) -> Vec<(Move, u64)> {
+    board.get_valid_moves()
+        .into_iter()
+        .map(|m| {
+            let mut next = board.clone();
+            let _ = next.make_move(&m);
+            let nodes = perft(&next, depth.saturating_sub(1));
+            (m, nodes)
+        })
+        .collect()
+}
+
+// A position with its known perft counts at depths 1, 2, ...
+pub struct PerftCase {
+    pub fen: &'static str,
+    pub counts: &'static [u64],
+}
+
+// Reference positions for each variant's move generator. The American and
+// international start counts are the published ones; the others pin down
+// crowning, flying kings and capture choice so a generator change that moves
+// them has to be explained.
+pub fn suite(variant: Variant) -> &'static [PerftCase] {
+    match variant {
+        Variant::American => &[
+            PerftCase { fen: "W:W21-32:B1-12", counts: &[7, 49, 302, 1469, 7361, 36768, 179740, 845931] },
+            PerftCase { fen: "W:WK14,K19,27,30:BK3,K5,12,16", counts: &[11, 32, 227, 1235, 8572, 49358, 337528] },
+            // A king jumping a full circle back to its own square
+            PerftCase { fen: "W:WK10,K18,K19,22:BK14,K15,6,7", counts: &[4, 5, 16, 120, 586, 3234] },
+            PerftCase { fen: "W:W11,25,30:B7,9,18,20", counts: &[1, 5, 21, 83, 375, 1493, 6708] },
+        ],
+        Variant::International => &[
+            PerftCase { fen: "W:W31-50:B1-20", counts: &[9, 81, 658, 4265, 27117, 167140] },
+            PerftCase { fen: "W:WK46,33:B28,19,9,40", counts: &[1, 2, 22, 33, 398] },
+            PerftCase { fen: "W:W28,32,33,37,38,42,43,47:BK5,12,13,17,18,21,23", counts: &[1, 1, 7, 51, 286, 2088] },
+        ],
+        Variant::Russian => &[
+            PerftCase { fen: "W:W21-32:B1-12", counts: &[7, 49, 302, 1469, 7482, 37986, 190146, 929901] },
+            // 11 is crowned on 2 and carries on to take 9 as a king
+            PerftCase { fen: "W:W11,25,30:B7,9,18,20", counts: &[1, 3, 23, 66, 395, 1126, 8275] },
+            PerftCase { fen: "W:WK29,22,23:B6,10,K18", counts: &[2, 3, 13, 35, 263, 1188] },
+        ],
+        Variant::Brazilian => &[
+            PerftCase { fen: "W:W21-32:B1-12", counts: &[7, 49, 302, 1469, 7473, 37628, 187302, 907830] },
+            PerftCase { fen: "W:W11,25,30:B7,9,18,20", counts: &[1, 5, 21, 59, 317, 1018, 5527] },
+            PerftCase { fen: "W:WK29,22,23:B6,10,K18", counts: &[1, 2, 6, 10, 63, 69] },
+        ],
+        Variant::Italian => &[
+            PerftCase { fen: "W:W21-32:B1-12", counts: &[7, 49, 302, 1469, 7361, 36473, 177532, 828783] },
+            // The king on 23 has to make the capture, and 22 may not take kings
+            PerftCase { fen: "W:W22,K23:B18,19,6,7", counts: &[1, 4, 24, 84, 331, 1076] },
+            PerftCase { fen: "W:W22,25,30:BK18,19,10,12", counts: &[1, 6, 23, 83, 340, 1481] },
+        ],
+        Variant::Pool => &[
+            PerftCase { fen: "W:W21-32:B1-12", counts: &[7, 49, 302, 1469, 7482, 37986, 190146, 929896] },
+            // 11 passes 2 mid-capture and stays a man, unlike Russian
+            PerftCase { fen: "W:W11,25,30:B7,9,18,20", counts: &[1, 5, 22, 62, 335, 1054, 5959] },
+            PerftCase { fen: "W:WK29,22,23:B6,10,K18", counts: &[2, 3, 13, 35, 263, 1188, 7766] },
+            PerftCase { fen: "W:W22,K31:B18,27,3", counts: &[3, 5, 29, 146, 1002, 6567, 43062] },
+        ],
+        Variant::Czech => &[
+            PerftCase { fen: "W:W21-32:B1-12", counts: &[7, 49, 302, 1469, 7361, 36768, 179740, 845931] },
+            // The king on 31 can capture, so 22 may not
+            PerftCase { fen: "W:W22,K31:B18,27,3", counts: &[2, 2, 16, 61, 457, 2701, 18689] },
+            PerftCase { fen: "W:WK29,22,23:B6,10,K18", counts: &[2, 3, 17, 48, 338, 1474, 9456] },
+            PerftCase { fen: "W:WK14,K19,27,30:BK3,K5,12,16", counts: &[19, 74, 650, 3930, 29623, 279896] },
+        ],
+        // Same moves as American, only the goal differs
+        Variant::Giveaway => &[
+            PerftCase { fen: "W:W21-32:B1-12", counts: &[7, 49, 302, 1469, 7361, 36768, 179740, 845931] },
+            PerftCase { fen: "W:WK14,K19,27,30:BK3,K5,12,16", counts: &[11, 32, 227, 1235, 8572, 49358, 337528] },
+        ],
+        Variant::Spanish => &[
+            PerftCase { fen: "W:W21-32:B1-12", counts: &[7, 49, 302, 1469, 7361, 36473, 177532, 828783] },
+            PerftCase { fen: "W:W22,K23:B18,19,6,7", counts: &[2, 2, 10, 18, 159, 986] },
+            // Taking the king on 18 beats taking the man on 19
+            PerftCase { fen: "W:W22,25,30:BK18,19,10,12", counts: &[1, 4, 20, 61, 302, 960] },
+        ],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suite_counts_match_at_shallow_depths() {
+        for variant in Variant::ALL {
+            for case in suite(variant) {
+                let board = Board::from_variant_fen(variant, case.fen).unwrap();
+                for (depth, &expected) in case.counts.iter().enumerate().take(5) {
+                    assert_eq!(perft(&board, depth as u32 + 1), expected, "{} {} perft({})", variant, case.fen, depth + 1);
+                }
+            }
+        }
+    }
+}
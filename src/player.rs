@@ -0,0 +1,401 @@
+use std::panic;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use crate::ab_ai::AlphaBetaEngine;
+use crate::ai::parallel_loss_function;
+use crate::board::{Board, Color};
+use crate::evaluator::{Evaluator, Heuristic};
+use crate::mcts::{MctsConfig, MctsEngine};
+use crate::mv::Move;
+use crate::search::{SearchLimits, SearchResult, StopHandle};
+
+// Who plays one side of a game
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlayerKind {
+    Human,
+    AlphaBeta,  // Iterative deepening alpha-beta, honors depth and time
+    Mcts,       // Monte Carlo tree search, honors time or playouts
+    Parallel,   // The parallel loss function, fixed depth only
+}
+
+impl PlayerKind {
+    pub const NAMES: [&'static str; 4] = ["human", "alphabeta", "mcts", "parallel"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "human" => Some(PlayerKind::Human),
+            "alphabeta" | "ab" => Some(PlayerKind::AlphaBeta),
+            "mcts" => Some(PlayerKind::Mcts),
+            "parallel" | "par" => Some(PlayerKind::Parallel),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PlayerKind::Human => "human",
+            PlayerKind::AlphaBeta => "alphabeta",
+            PlayerKind::Mcts => "mcts",
+            PlayerKind::Parallel => "parallel",
+        }
+    }
+}
+
+// The searchers and their state. A ponder search takes them to its thread
+// and hands them back when it is resolved.
+struct Engines {
+    alpha_beta: AlphaBetaEngine,
+    mcts: MctsEngine,
+    evaluator: Box<dyn Evaluator + Send>,
+}
+
+impl Engines {
+    fn search<F: FnMut(&SearchResult)>(&mut self, kind: PlayerKind, board: &Board, limits: &SearchLimits, mut on_iteration: F) -> SearchResult {
+        let evaluator = self.evaluator.as_ref();
+
+        let result = match kind {
+            // A human side asking for a hint gets the alpha-beta answer
+            PlayerKind::AlphaBeta | PlayerKind::Human => {
+                return self.alpha_beta.search(board, limits, evaluator, on_iteration);
+            },
+            PlayerKind::Mcts => return self.mcts.search(board, limits, evaluator, on_iteration),
+            PlayerKind::Parallel => {
+                let start = Instant::now();
+                let depth = limits.max_depth().min(SearchLimits::DEFAULT_DEPTH);
+                let best_move = parallel_loss_function(board, depth, evaluator);
+
+                // The parallel search doesn't report a score, so show the
+                // static evaluation after its move
+                let score = best_move.as_ref()
+                    .map(|m| {
+                        let mut next = board.clone();
+                        let _ = next.make_move(m);
+                        -evaluator.evaluate(&next)
+                    })
+                    .unwrap_or(0.0);
+
+                SearchResult {
+                    pv: best_move.clone().into_iter().collect(),
+                    best_move,
+                    score,
+                    depth,
+                    nodes: 0,
+                    elapsed: start.elapsed(),
+                }
+            },
+        };
+
+        on_iteration(&result);
+        result
+    }
+}
+
+// A search of the position after our move and the reply the PV predicts,
+// running while the opponent thinks
+struct Ponder {
+    board: Board,
+    stop: StopHandle,
+    updates: mpsc::Receiver<SearchResult>,
+    thread: JoinHandle<(Engines, SearchResult)>,
+}
+
+// An engine with its own state (hash table, MCTS trees), so two engines
+// playing each other don't share anything
+pub struct EnginePlayer {
+    pub kind: PlayerKind,
+    pub limits: SearchLimits,
+    pub ponder: bool,         // Keep searching on the opponent's time
+    pub ponder_hits: u32,
+    pub ponder_misses: u32,
+    engines: Option<Engines>, // None only while a ponder search has them
+    pondering: Option<Ponder>,
+}
+
+impl EnginePlayer {
+    pub fn new(kind: PlayerKind, limits: SearchLimits, hash_mb: usize) -> Self {
+        EnginePlayer {
+            kind,
+            limits,
+            ponder: false,
+            ponder_hits: 0,
+            ponder_misses: 0,
+            engines: Some(Engines {
+                alpha_beta: AlphaBetaEngine::new(hash_mb),
+                mcts: MctsEngine::new(MctsConfig::default()),
+                evaluator: Box::new(Heuristic::default()),
+            }),
+            pondering: None,
+        }
+    }
+
+    pub fn with_evaluator(mut self, evaluator: Box<dyn Evaluator + Send>) -> Self {
+        self.engines_mut().evaluator = evaluator;
+        self
+    }
+
+    pub fn with_ponder(mut self, ponder: bool) -> Self {
+        self.ponder = ponder;
+        self
+    }
+
+    fn engines_mut(&mut self) -> &mut Engines {
+        self.stop_pondering();
+        self.engines.as_mut().expect("the engines are back once pondering stops")
+    }
+
+    // Forget what was learned about the previous game
+    pub fn new_game(&mut self) {
+        let engines = self.engines_mut();
+        engines.alpha_beta.clear();
+        engines.mcts.clear();
+    }
+
+    pub fn choose(&mut self, board: &Board) -> SearchResult {
+        self.search(board, |_| {})
+    }
+
+    // Like choose, reporting progress as it goes: after every iteration for
+    // alpha-beta, once at the end for the other engines. With pondering on,
+    // a search of the predicted reply keeps running after it returns.
+    pub fn search<F: FnMut(&SearchResult)>(&mut self, board: &Board, mut on_iteration: F) -> SearchResult {
+        let result = match self.ponder_hit(board, &mut on_iteration) {
+            Some(result) => result,
+            None => {
+                let (kind, limits) = (self.kind, self.limits.clone());
+                self.engines_mut().search(kind, board, &limits, on_iteration)
+            },
+        };
+
+        if self.ponder && matches!(self.kind, PlayerKind::AlphaBeta | PlayerKind::Mcts) {
+            self.start_pondering(board, &result);
+        }
+        result
+    }
+
+    // Search the position after our move and the predicted reply. Without a
+    // time limit that search stops by itself at the usual depth or node
+    // count; with one it runs until the opponent moves.
+    fn start_pondering(&mut self, board: &Board, result: &SearchResult) {
+        let (Some(ours), Some(reply)) = (result.best_move.as_ref(), result.pv.get(1)) else {
+            return;
+        };
+        let mut predicted = board.clone();
+        if predicted.make_move(ours).is_err() || !predicted.get_valid_moves().contains(reply) {
+            return;
+        }
+        let _ = predicted.make_move(reply);
+        if predicted.is_game_over() || predicted.get_valid_moves().is_empty() {
+            return;
+        }
+
+        let stop = StopHandle::new();
+        let limits = if self.limits.movetime.is_some() {
+            SearchLimits { movetime: None, infinite: true, stop: Some(stop.clone()), ..self.limits.clone() }
+        } else {
+            SearchLimits { depth: Some(self.limits.max_depth()), stop: Some(stop.clone()), ..self.limits.clone() }
+        };
+
+        let mut engines = self.engines.take().expect("not already pondering");
+        let (sender, updates) = mpsc::channel();
+        let (kind, position) = (self.kind, predicted.clone());
+        let thread = thread::spawn(move || {
+            let result = engines.search(kind, &position, &limits, |result| {
+                let _ = sender.send(result.clone());
+            });
+            (engines, result)
+        });
+
+        self.pondering = Some(Ponder { board: predicted, stop, updates, thread });
+    }
+
+    // If the opponent played the predicted reply, the ponder search becomes
+    // the real one: it gets this move's time from now, and what it found so
+    // far is reported before it carries on. Otherwise it is thrown away.
+    fn ponder_hit<F: FnMut(&SearchResult)>(&mut self, board: &Board, on_iteration: &mut F) -> Option<SearchResult> {
+        let ponder = self.pondering.as_ref()?;
+        if ponder.board.squares != board.squares || ponder.board.turn != board.turn {
+            self.ponder_misses += 1;
+            self.stop_pondering();
+            return None;
+        }
+
+        let ponder = self.pondering.take()?;
+        self.ponder_hits += 1;
+        if let Some(movetime) = self.limits.movetime {
+            ponder.stop.set_deadline(Some(Instant::now() + movetime));
+        }
+
+        loop {
+            match ponder.updates.recv_timeout(Duration::from_millis(10)) {
+                Ok(result) => on_iteration(&result),
+                Err(RecvTimeoutError::Timeout) => {
+                    // Whoever could stop the real search can stop this one
+                    if self.limits.stop.as_ref().is_some_and(StopHandle::is_stopped) {
+                        ponder.stop.stop();
+                    }
+                },
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+
+        let (engines, result) = ponder.thread.join().unwrap_or_else(|panic| panic::resume_unwind(panic));
+        self.engines = Some(engines);
+        Some(result)
+    }
+
+    pub fn stop_pondering(&mut self) {
+        if let Some(ponder) = self.pondering.take() {
+            ponder.stop.stop();
+            let (engines, _) = ponder.thread.join().unwrap_or_else(|panic| panic::resume_unwind(panic));
+            self.engines = Some(engines);
+        }
+    }
+}
+
+impl Drop for EnginePlayer {
+    fn drop(&mut self) {
+        if let Some(ponder) = self.pondering.take() {
+            ponder.stop.stop();
+            let _ = ponder.thread.join();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum GameResult {
+    Win(Color),
+    Draw,  // Reached the ply limit
+}
+
+// A finished game: where it started, the moves played and how it ended
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GameRecord {
+    pub start: Board,
+    pub moves: Vec<Move>,
+    pub result: GameResult,
+}
+
+// Play two engines against each other from `board`. on_move sees the
+// position before each move along with the search that chose it.
+pub fn play_engines<F>(board: &Board, red: &mut EnginePlayer, black: &mut EnginePlayer, max_plies: u32, mut on_move: F) -> GameRecord
+where
+    F: FnMut(&Board, &Move, &SearchResult),
+{
+    let start = board.clone();
+    let mut board = board.clone();
+    let mut moves = Vec::new();
+    let record = |moves, result| GameRecord { start: start.clone(), moves, result };
+
+    for _ in 0..max_plies {
+        // Whoever has to move here is out of pieces or blocked
+        if let Some(winner) = board.winner() {
+            return record(moves, GameResult::Win(winner));
+        }
+
+        let player = match board.turn {
+            Color::Red => &mut *red,
+            Color::Black => &mut *black,
+        };
+        let result = player.choose(&board);
+        let Some(m) = result.best_move.clone() else {
+            return record(moves, GameResult::Win(board.turn.toggle()));
+        };
+
+        on_move(&board, &m, &result);
+        if board.make_move(&m).is_err() {
+            return record(moves, GameResult::Win(board.turn.toggle()));
+        }
+        moves.push(m);
+    }
+
+    record(moves, GameResult::Draw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pondering(limits: SearchLimits) -> EnginePlayer {
+        EnginePlayer::new(PlayerKind::AlphaBeta, limits, 1).with_ponder(true)
+    }
+
+    // The position after `first`'s move and the reply its PV predicts
+    fn predicted(board: &Board, first: &SearchResult) -> Board {
+        let mut board = board.clone();
+        for m in &first.pv[..2] {
+            board.make_move(m).unwrap();
+        }
+        board
+    }
+
+    #[test]
+    fn the_predicted_reply_continues_the_ponder_search() {
+        let board = Board::new();
+        let mut player = pondering(SearchLimits::depth(5));
+        let first = player.choose(&board);
+        assert!(player.pondering.is_some());
+
+        let next = predicted(&board, &first);
+        let mut updates = 0;
+        let result = player.search(&next, |_| updates += 1);
+
+        assert_eq!((player.ponder_hits, player.ponder_misses), (1, 0));
+        assert_eq!(result.depth, 5);
+        assert!(updates > 0);
+        assert!(next.get_valid_moves().contains(result.best_move.as_ref().unwrap()));
+        assert!(player.pondering.is_some(), "pondering carries on after the hit");
+    }
+
+    #[test]
+    fn another_reply_throws_the_ponder_search_away() {
+        let board = Board::new();
+        let mut player = pondering(SearchLimits::depth(5));
+        let first = player.choose(&board);
+
+        let mut next = board.clone();
+        next.make_move(first.best_move.as_ref().unwrap()).unwrap();
+        let other = next.get_valid_moves().into_iter().find(|m| Some(m) != first.pv.get(1)).unwrap();
+        next.make_move(&other).unwrap();
+
+        let result = player.choose(&next);
+        assert_eq!((player.ponder_hits, player.ponder_misses), (0, 1));
+        assert_eq!(result.depth, 5);
+        assert!(next.get_valid_moves().contains(result.best_move.as_ref().unwrap()));
+
+        // A new game leaves nothing running
+        player.new_game();
+        assert!(player.pondering.is_none() && player.engines.is_some());
+    }
+
+    #[test]
+    fn a_hit_gets_the_full_move_time_from_then() {
+        let board = Board::new();
+        let mut player = pondering(SearchLimits::movetime(Duration::from_millis(150)));
+        let first = player.choose(&board);
+        let next = predicted(&board, &first);
+
+        // The ponder search has no deadline of its own while it waits
+        thread::sleep(Duration::from_millis(300));
+        let hit = Instant::now();
+        player.choose(&next);
+        let elapsed = hit.elapsed();
+        assert_eq!(player.ponder_hits, 1);
+        assert!(elapsed >= Duration::from_millis(120) && elapsed < Duration::from_secs(5), "{:?}", elapsed);
+    }
+
+    #[test]
+    fn engines_that_cannot_ponder_never_start() {
+        let board = Board::new();
+        let mut parallel = EnginePlayer::new(PlayerKind::Parallel, SearchLimits::depth(2), 1).with_ponder(true);
+        parallel.choose(&board);
+        assert!(parallel.pondering.is_none());
+
+        let mut off = EnginePlayer::new(PlayerKind::AlphaBeta, SearchLimits::depth(2), 1);
+        off.choose(&board);
+        assert!(off.pondering.is_none());
+    }
+}
@@ -0,0 +1,652 @@
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use crate::ab_ai::AlphaBetaEngine;
+use crate::board::Board;
+use crate::evaluator::{Evaluator, Heuristic, MaterialOnly};
+use crate::mcts::{MctsConfig, MctsEngine};
+use crate::mv::Move;
+use crate::nnue::NnueEvaluator;
+use crate::search::{SearchLimits, SearchResult, StopHandle};
+use crate::variant::Variant;
+use crate::weights::EvalWeights;
+
+// Line based engine protocol on stdin/stdout, modelled on the Hub protocol
+// used by draughts engines. Every line is a command followed by `key=value`
+// arguments (values with spaces are quoted):
+//
+//   hub                          -> id ..., param ..., wait
+//   init                         -> ready
+//   ping                         -> pong
+//   set-param name=N value=V     hash (MB), threads, engine, eval, weights,
+//                                network, variant (resets to its start position)
+//   new-game                     start position, clear hash and trees
+//   pos [start] [fen=F] [moves="M1 M2"]
+//   move M1 [M2 ...]             play moves on the current position
+//   level [depth=N] [nodes=N] [move-time=S] [time=S] [inc=S] [moves=N]
+//   go [think|ponder|analyze]    -> info ..., bestmove move=M [ponder=M]
+//   ponder-hit                   the pondered move was played, search for real
+//   stop                         -> bestmove now
+//   quit
+//
+// Moves are written in numeric notation ("11-15", "15x24"); algebraic input
+// ("E3-F4") is accepted too.
+//
+// A running search (go ponder/analyze in particular) keeps going while other
+// commands arrive; only those that change the position or the engines
+// (set-param, new-game, pos, move, go) stop it first. Parameters are applied
+// when the next search starts, so they can be sent in any order.
+
+pub const ENGINE_NAME: &str = "checkersBot";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EngineKind {
+    AlphaBeta,
+    Mcts,
+}
+
+impl EngineKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "alphabeta" | "ab" => Some(EngineKind::AlphaBeta),
+            "mcts" => Some(EngineKind::Mcts),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EngineKind::AlphaBeta => "alphabeta",
+            EngineKind::Mcts => "mcts",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Think,
+    Ponder,
+    Analyze,
+}
+
+// Time control set with `level`
+#[derive(Debug, Clone, Default)]
+struct Level {
+    depth: Option<u32>,
+    nodes: Option<u64>,
+    movetime: Option<Duration>,
+    time_left: Option<Duration>,
+    increment: Duration,
+    moves_to_go: Option<u32>,
+}
+
+impl Level {
+    // Thinking time for one move, if the level is time based
+    fn allocation(&self) -> Option<Duration> {
+        if self.movetime.is_some() {
+            return self.movetime;
+        }
+
+        self.time_left.map(|left| {
+            let share = left / self.moves_to_go.unwrap_or(30).max(1) + self.increment * 3 / 4;
+            share.min(left / 2)
+        })
+    }
+
+    fn limits(&self) -> SearchLimits {
+        SearchLimits {
+            depth: self.depth,
+            nodes: self.nodes,
+            movetime: self.allocation(),
+            ..Default::default()
+        }
+    }
+}
+
+// Where the protocol's replies go, one line at a time
+pub type Output = Arc<dyn Fn(&str) + Send + Sync>;
+
+// Everything a search needs; it moves into the search thread and comes back
+// when the thread is joined
+struct Engines {
+    kind: EngineKind,
+    alpha_beta: AlphaBetaEngine,
+    mcts: MctsEngine,
+    evaluator: Arc<dyn Evaluator + Send>,
+    pool: rayon::ThreadPool,
+}
+
+impl Engines {
+    fn search(&mut self, board: &Board, limits: &SearchLimits, send: &Output) -> SearchResult {
+        let evaluator = self.evaluator.clone();
+        let (kind, alpha_beta, mcts) = (self.kind, &mut self.alpha_beta, &mut self.mcts);
+
+        self.pool.install(|| match kind {
+            EngineKind::AlphaBeta => alpha_beta.search(board, limits, evaluator.as_ref(), |result| {
+                send(&info_line(board, result));
+            }),
+            EngineKind::Mcts => mcts.search(board, limits, evaluator.as_ref(), |result| {
+                send(&info_line(board, result));
+            }),
+        })
+    }
+}
+
+struct Running {
+    stop: StopHandle,
+    ponder_hit: Arc<AtomicBool>,
+    quiet: Arc<AtomicBool>,  // Abandoned for a new search, so no bestmove
+    mode: Mode,
+    thread: JoinHandle<Engines>,
+}
+
+pub struct Protocol {
+    board: Board,
+    variant: Variant,
+    level: Level,
+    kind: EngineKind,
+    hash_mb: usize,
+    threads: usize,
+    eval_name: String,
+    weights: Option<String>,
+    network: Option<String>,
+    engines: Option<Engines>,
+    rebuild: bool,  // Parameters changed since the engines were built
+    running: Option<Running>,
+    send: Output,
+}
+
+fn stdout_line(line: &str) {
+    let mut out = io::stdout().lock();
+    let _ = writeln!(out, "{}", line);
+    let _ = out.flush();
+}
+
+fn format_score(score: f32) -> String {
+    match score {
+        s if s == f32::INFINITY => "win".to_string(),
+        s if s == f32::NEG_INFINITY => "loss".to_string(),
+        s => format!("{:.2}", s),
+    }
+}
+
+pub fn info_line(board: &Board, result: &SearchResult) -> String {
+    let seconds = result.elapsed.as_secs_f64();
+    let nps = if seconds > 0.0 { (result.nodes as f64 / seconds) as u64 } else { 0 };
+    format!(
+        "info depth={} score={} nodes={} time={:.3} nps={} pv=\"{}\"",
+        result.depth,
+        format_score(result.score),
+        result.nodes,
+        seconds,
+        nps,
+        pv_string(board, &result.pv)
+    )
+}
+
+// Moves of a line in numeric notation, checked against the position they
+// are played from
+fn pv_string(board: &Board, pv: &[Move]) -> String {
+    let mut board = board.clone();
+    pv.iter()
+        .map_while(|m| {
+            let text = m.to_numeric();
+            board.make_move(m).ok().map(|_| text)
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn bestmove_line(result: &SearchResult) -> String {
+    match &result.best_move {
+        Some(best) => match result.pv.get(1) {
+            Some(reply) => format!("bestmove move={} ponder={}", best.to_numeric(), reply.to_numeric()),
+            None => format!("bestmove move={}", best.to_numeric()),
+        },
+        None => "bestmove move=none".to_string(),
+    }
+}
+
+// Split a command line into the command and its arguments. Arguments are
+// `key=value`, `key="quoted value"` or bare words (value "").
+pub fn tokenize(line: &str) -> (String, Vec<(String, String)>) {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in line.trim().chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            },
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    let command = if tokens.is_empty() { String::new() } else { tokens.remove(0) };
+    let args = tokens.into_iter()
+        .map(|t| match t.split_once('=') {
+            Some((k, v)) => (k.to_string(), v.to_string()),
+            None => (t, String::new()),
+        })
+        .collect();
+
+    (command, args)
+}
+
+fn arg<'a>(args: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    args.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+}
+
+fn seconds(value: &str) -> Result<Duration, String> {
+    value.parse::<f64>()
+        .ok()
+        .filter(|s| *s >= 0.0)
+        .map(Duration::from_secs_f64)
+        .ok_or_else(|| format!("invalid time '{}'", value))
+}
+
+impl Default for Protocol {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Protocol {
+    pub fn new() -> Self {
+        Self::with_output(Arc::new(stdout_line))
+    }
+
+    // A protocol that hands its replies to `send` instead of stdout
+    pub fn with_output(send: Output) -> Self {
+        Protocol {
+            board: Board::new(),
+            variant: Variant::American,
+            level: Level::default(),
+            kind: EngineKind::AlphaBeta,
+            hash_mb: 64,
+            threads: rayon::current_num_threads().max(1),
+            eval_name: "heuristic".to_string(),
+            weights: None,
+            network: None,
+            engines: None,
+            rebuild: true,
+            running: None,
+            send,
+        }
+    }
+
+    fn build_evaluator(&self) -> Result<Arc<dyn Evaluator + Send>, String> {
+        let weights = match &self.weights {
+            Some(path) => EvalWeights::load(path)?,
+            None => EvalWeights::default(),
+        };
+
+        match self.eval_name.as_str() {
+            "heuristic" => Ok(Arc::new(Heuristic::new(weights))),
+            "material" => Ok(Arc::new(MaterialOnly)),
+            "nnue" => match &self.network {
+                Some(path) => Ok(Arc::new(NnueEvaluator::load(path)?)),
+                None => Err("eval=nnue needs the network parameter".to_string()),
+            },
+            other => Err(format!("unknown eval '{}'", other)),
+        }
+    }
+
+    fn build_engines(&self) -> Result<Engines, String> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads)
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        Ok(Engines {
+            kind: self.kind,
+            alpha_beta: AlphaBetaEngine::new(self.hash_mb),
+            mcts: MctsEngine::new(MctsConfig { threads: self.threads, ..MctsConfig::default() }),
+            evaluator: self.build_evaluator()?,
+            pool,
+        })
+    }
+
+    // Stop any running search and get the engines back
+    fn finish_search(&mut self) {
+        if let Some(running) = self.running.take() {
+            running.stop.stop();
+            if let Ok(engines) = running.thread.join() {
+                self.engines = Some(engines);
+            }
+        }
+    }
+
+    // Record a parameter. Files are only read when the engines are rebuilt
+    // for the next search, so eval=nnue may come before network.
+    fn set_param(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name {
+            "hash" => {
+                self.hash_mb = value.parse().map_err(|_| format!("invalid hash size '{}'", value))?;
+            },
+            "threads" => {
+                self.threads = value.parse::<usize>().ok().filter(|&t| t > 0)
+                    .ok_or_else(|| format!("invalid thread count '{}'", value))?;
+            },
+            "engine" => {
+                self.kind = EngineKind::from_name(value).ok_or_else(|| format!("unknown engine '{}'", value))?;
+                if let Some(engines) = self.engines.as_mut() {
+                    engines.kind = self.kind;
+                }
+                return Ok(());
+            },
+            "eval" => match value {
+                "heuristic" | "material" | "nnue" => self.eval_name = value.to_string(),
+                other => return Err(format!("unknown eval '{}'", other)),
+            },
+            "weights" => self.weights = Some(value.to_string()).filter(|v| !v.is_empty()),
+            "network" => self.network = Some(value.to_string()).filter(|v| !v.is_empty()),
+            "variant" => {
+                self.variant = Variant::from_name(value).ok_or_else(|| format!("unknown variant '{}'", value))?;
+                self.board = Board::for_variant(self.variant);
+            },
+            _ => return Err(format!("unknown parameter '{}'", name)),
+        }
+
+        self.rebuild = true;
+        Ok(())
+    }
+
+    // Play a list of moves on a copy of the board, so nothing changes if
+    // one of them is illegal
+    fn play_moves(board: &Board, moves: &str) -> Result<Board, String> {
+        let mut board = board.clone();
+        for text in moves.split_whitespace() {
+            let m = Move::parse(text, &board)
+                .ok_or_else(|| format!("illegal move '{}'", text))?;
+            board.make_move(&m)?;
+        }
+        Ok(board)
+    }
+
+    fn set_position(&mut self, args: &[(String, String)]) -> Result<(), String> {
+        let board = match arg(args, "fen") {
+            Some(fen) => Board::from_variant_fen(self.variant, fen)?,
+            None => Board::for_variant(self.variant),
+        };
+
+        self.board = Self::play_moves(&board, arg(args, "moves").unwrap_or(""))?;
+        Ok(())
+    }
+
+    fn set_level(&mut self, args: &[(String, String)]) -> Result<(), String> {
+        let mut level = Level::default();
+
+        for (key, value) in args {
+            match key.as_str() {
+                "depth" => level.depth = Some(value.parse().map_err(|_| format!("invalid depth '{}'", value))?),
+                "nodes" => level.nodes = Some(value.parse().map_err(|_| format!("invalid nodes '{}'", value))?),
+                "move-time" => level.movetime = Some(seconds(value)?),
+                "time" => level.time_left = Some(seconds(value)?),
+                "inc" => level.increment = seconds(value)?,
+                "moves" => level.moves_to_go = Some(value.parse().map_err(|_| format!("invalid moves '{}'", value))?),
+                other => return Err(format!("unknown level argument '{}'", other)),
+            }
+        }
+
+        self.level = level;
+        Ok(())
+    }
+
+    fn go(&mut self, mode: Mode) -> Result<(), String> {
+        if self.rebuild || self.engines.is_none() {
+            self.engines = Some(self.build_engines()?);
+            self.rebuild = false;
+        }
+        let mut engines = self.engines.take().ok_or("engine is not configured")?;
+
+        let stop = StopHandle::new();
+        let ponder_hit = Arc::new(AtomicBool::new(false));
+        let quiet = Arc::new(AtomicBool::new(false));
+
+        let mut limits = match mode {
+            Mode::Think => self.level.limits(),
+            Mode::Ponder | Mode::Analyze => SearchLimits { infinite: true, ..Default::default() },
+        };
+        limits.stop = Some(stop.clone());
+
+        let board = self.board.clone();
+        let thread_stop = stop.clone();
+        let thread_hit = ponder_hit.clone();
+        let thread_quiet = quiet.clone();
+        let send = self.send.clone();
+
+        let thread = thread::spawn(move || {
+            let result = engines.search(&board, &limits, &send);
+
+            // In ponder and analyze mode the answer is only given once the
+            // GUI asks for it
+            while mode != Mode::Think && !thread_stop.is_stopped()
+                && !(mode == Mode::Ponder && thread_hit.load(Ordering::SeqCst)) {
+                thread::sleep(Duration::from_millis(5));
+            }
+
+            if !thread_quiet.load(Ordering::SeqCst) {
+                send(&bestmove_line(&result));
+            }
+            engines
+        });
+
+        self.running = Some(Running { stop, ponder_hit, quiet, mode, thread });
+        Ok(())
+    }
+
+    // The predicted move was played: the ponder search becomes a normal
+    // search with this move's time allocation, counted from now. Depth and
+    // node levels have no allocation, so the ponder search is replaced by a
+    // search to the level's limits; the hash table keeps what it found.
+    fn ponder_hit(&mut self) -> Result<(), String> {
+        let Some(running) = self.running.as_ref().filter(|r| r.mode == Mode::Ponder) else { return Ok(()) };
+
+        match self.level.allocation() {
+            Some(time) => {
+                running.ponder_hit.store(true, Ordering::SeqCst);
+                running.stop.set_deadline(Some(Instant::now() + time));
+                Ok(())
+            },
+            None => {
+                running.quiet.store(true, Ordering::SeqCst);
+                self.finish_search();
+                self.go(Mode::Think)
+            },
+        }
+    }
+
+    // Handle one command line; returns false on quit
+    pub fn handle(&mut self, line: &str) -> bool {
+        let (command, args) = tokenize(line);
+
+        // Only commands that change the position or the engines wait for a
+        // running search; the others leave it running
+        match command.as_str() {
+            "" => return true,
+            "quit" => {
+                self.finish_search();
+                return false;
+            },
+            "stop" | "set-param" | "new-game" | "pos" | "move" | "go" => self.finish_search(),
+            _ => {},
+        }
+
+        let outcome: Result<(), String> = match command.as_str() {
+            "ping" => {
+                (self.send)("pong");
+                Ok(())
+            },
+            "stop" => Ok(()),
+            "ponder-hit" => self.ponder_hit(),
+            "hub" => {
+                let variants: Vec<&str> = Variant::ALL.iter().map(|v| v.name()).collect();
+                (self.send)(&format!("id name={} version={}", ENGINE_NAME, env!("CARGO_PKG_VERSION")));
+                (self.send)(&format!("param name=variant value={} type=enum values=\"{}\"", self.variant.name(), variants.join(" ")));
+                (self.send)(&format!("param name=hash value={} type=int min=0 max=65536", self.hash_mb));
+                (self.send)(&format!("param name=threads value={} type=int min=1 max=256", self.threads));
+                (self.send)(&format!("param name=engine value={} type=enum values=\"alphabeta mcts\"", self.kind.name()));
+                (self.send)(&format!("param name=eval value={} type=enum values=\"heuristic material nnue\"", self.eval_name));
+                (self.send)("param name=weights value=\"\" type=string");
+                (self.send)("param name=network value=\"\" type=string");
+                (self.send)("wait");
+                Ok(())
+            },
+            "init" => {
+                (self.send)("ready");
+                Ok(())
+            },
+            "set-param" => match (arg(&args, "name"), arg(&args, "value")) {
+                (Some(name), Some(value)) => self.set_param(name, value),
+                _ => Err("set-param needs name and value".to_string()),
+            },
+            "new-game" => {
+                self.board = Board::for_variant(self.variant);
+                if let Some(engines) = self.engines.as_mut() {
+                    engines.alpha_beta.clear();
+                    engines.mcts.clear();
+                }
+                Ok(())
+            },
+            "pos" => self.set_position(&args),
+            "move" => {
+                let moves: Vec<&str> = args.iter().map(|(k, _)| k.as_str()).collect();
+                Self::play_moves(&self.board, &moves.join(" ")).map(|board| self.board = board)
+            },
+            "level" => self.set_level(&args),
+            "go" => match args.first().map(|(k, _)| k.as_str()).unwrap_or("think") {
+                "think" => self.go(Mode::Think),
+                "ponder" => self.go(Mode::Ponder),
+                "analyze" => self.go(Mode::Analyze),
+                other => Err(format!("unknown go mode '{}'", other)),
+            },
+            "fen" => {
+                (self.send)(&format!("fen {}", self.board.to_fen()));
+                Ok(())
+            },
+            other => Err(format!("unknown command '{}'", other)),
+        };
+
+        if let Err(e) = outcome {
+            (self.send)(&format!("error message=\"{}\"", e));
+        }
+        true
+    }
+}
+
+// Run the protocol until stdin closes or `quit` arrives
+pub fn run() {
+    let mut protocol = Protocol::new();
+    let stdin = io::stdin();
+
+    for line in stdin.lock().lines() {
+        let Ok(line) = line else { break };
+        if !protocol.handle(&line) {
+            return;
+        }
+    }
+
+    protocol.finish_search();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // A protocol whose replies are collected for the test to read
+    fn protocol() -> (Protocol, Arc<Mutex<Vec<String>>>) {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let sink = lines.clone();
+        let mut protocol = Protocol::with_output(Arc::new(move |line: &str| sink.lock().unwrap().push(line.to_string())));
+        protocol.handle("set-param name=threads value=1");
+        protocol.handle("set-param name=hash value=1");
+        (protocol, lines)
+    }
+
+    fn bestmoves(lines: &Mutex<Vec<String>>) -> usize {
+        lines.lock().unwrap().iter().filter(|l| l.starts_with("bestmove")).count()
+    }
+
+    // Wait until `count` bestmove lines have arrived, then take every line
+    fn collect(lines: &Mutex<Vec<String>>, count: usize) -> Vec<String> {
+        let deadline = Instant::now() + Duration::from_secs(20);
+        while bestmoves(lines) < count && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+        std::mem::take(&mut *lines.lock().unwrap())
+    }
+
+    fn last_depth(replies: &[String]) -> Option<u32> {
+        let info = replies.iter().rfind(|l| l.starts_with("info"))?;
+        tokenize(info).1.iter().find(|(k, _)| k == "depth")?.1.parse().ok()
+    }
+
+    #[test]
+    fn think_answers_once_the_level_is_reached() {
+        let (mut protocol, lines) = protocol();
+        protocol.handle("ping");
+        protocol.handle("level depth=3");
+        protocol.handle("go think");
+
+        let replies = collect(&lines, 1);
+        assert_eq!(replies[0], "pong");
+        assert_eq!(last_depth(&replies), Some(3));
+        assert!(replies.last().unwrap().starts_with("bestmove move="), "{:?}", replies);
+
+        protocol.handle("go sideways");
+        assert_eq!(collect(&lines, 0), ["error message=\"unknown go mode 'sideways'\""]);
+        assert!(!protocol.handle("quit"));
+    }
+
+    #[test]
+    fn ponder_waits_for_stop_or_a_hit() {
+        let (mut protocol, lines) = protocol();
+
+        // Pondering gives no answer until stop, which gives exactly one
+        protocol.handle("go ponder");
+        thread::sleep(Duration::from_millis(100));
+        protocol.handle("fen");
+        assert_eq!(bestmoves(&lines), 0);
+        protocol.handle("stop");
+        let replies = collect(&lines, 1);
+        assert_eq!(replies.iter().filter(|l| l.starts_with("bestmove")).count(), 1);
+        assert!(replies.iter().any(|l| l.starts_with("fen W:")));
+
+        // With a depth level a hit searches to that depth, not one ply less
+        // or however far the ponder search had got
+        protocol.handle("level depth=4");
+        protocol.handle("go ponder");
+        thread::sleep(Duration::from_millis(100));
+        protocol.handle("ponder-hit");
+        let replies = collect(&lines, 1);
+        assert_eq!(last_depth(&replies), Some(4), "{:?}", replies);
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(bestmoves(&lines), 0);
+
+        // With a time level a hit gives the search its move time from now
+        protocol.handle("level move-time=0.2");
+        protocol.handle("go ponder");
+        protocol.handle("ponder-hit");
+        let hit = Instant::now();
+        assert!(collect(&lines, 1).last().unwrap().starts_with("bestmove"));
+        assert!(hit.elapsed() >= Duration::from_millis(150), "{:?}", hit.elapsed());
+
+        // A hit while thinking changes nothing
+        protocol.handle("level depth=2");
+        protocol.handle("go");
+        protocol.handle("ponder-hit");
+        assert_eq!(last_depth(&collect(&lines, 1)), Some(2));
+        assert!(!protocol.handle("quit"));
+    }
+}
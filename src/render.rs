@@ -0,0 +1,279 @@
+use std::fmt;
+use crate::board::{Board, Color, Piece};
+use crate::mv::Move;
+
+// Board diagrams as strings, for logs, bug reports and the terminal. Each
+// square is three characters wide so the last move's path can be bracketed
+// and its captures marked with x:
+//
+//     A  B  C  D  E  F  G  H
+//   +------------------------+
+// 1 |    b     b     b     b |
+// 2 | b     b     b    [.]   |
+// 3 |    b     b     x     b |
+// 4 | .     .    [b]    .    |
+// ...
+//   +------------------------+
+// Red to move  W:W21,23,...:B1,2,...
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Glyphs {
+    #[default]
+    Ascii,    // r R b B, . for empty dark squares
+    Unicode,  // Draughts pieces and box drawing
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Renderer {
+    pub glyphs: Glyphs,
+    pub numbers: bool,            // Show 1-32 (1-50) on the empty dark squares
+    pub flipped: bool,            // Black's side at the bottom
+    pub last_move: Option<Move>,  // Bracket its path and mark its captures
+}
+
+struct Frame {
+    top: &'static str,
+    bottom: &'static str,
+    side: char,
+    horizontal: char,
+}
+
+impl Glyphs {
+    fn piece(&self, piece: Piece) -> char {
+        match (self, piece) {
+            (Glyphs::Ascii, Piece::Empty) => '.',
+            (Glyphs::Ascii, p) => p.to_char(),
+            (Glyphs::Unicode, Piece::RedMan) => '⛀',
+            (Glyphs::Unicode, Piece::RedKing) => '⛁',
+            (Glyphs::Unicode, Piece::BlackMan) => '⛂',
+            (Glyphs::Unicode, Piece::BlackKing) => '⛃',
+            (Glyphs::Unicode, Piece::Empty) => '·',
+        }
+    }
+
+    fn captured(&self) -> char {
+        match self {
+            Glyphs::Ascii => 'x',
+            Glyphs::Unicode => '×',
+        }
+    }
+
+    fn frame(&self) -> Frame {
+        match self {
+            Glyphs::Ascii => Frame { top: "+", bottom: "+", side: '|', horizontal: '-' },
+            Glyphs::Unicode => Frame { top: "┌┐", bottom: "└┘", side: '│', horizontal: '─' },
+        }
+    }
+}
+
+impl Frame {
+    fn line(&self, corners: &str, margin: usize, size: usize) -> String {
+        let mut corners = corners.chars();
+        let left = corners.next().unwrap_or('+');
+        let right = corners.next().unwrap_or(left);
+        format!("{:margin$} {}{}{}\n", "", left, self.horizontal.to_string().repeat(size * 3), right)
+    }
+}
+
+impl Renderer {
+    pub fn ascii() -> Self {
+        Renderer::default()
+    }
+
+    pub fn unicode() -> Self {
+        Renderer { glyphs: Glyphs::Unicode, ..Default::default() }
+    }
+
+    pub fn numbered(mut self) -> Self {
+        self.numbers = true;
+        self
+    }
+
+    pub fn flipped(mut self, flipped: bool) -> Self {
+        self.flipped = flipped;
+        self
+    }
+
+    // Show Black's side at the bottom when Black is the one looking
+    pub fn facing(self, color: Color) -> Self {
+        self.flipped(color == Color::Black)
+    }
+
+    pub fn highlight(mut self, m: &Move) -> Self {
+        self.last_move = Some(m.clone());
+        self
+    }
+
+    // A comma separated list of options, e.g. "unicode,numbers,flipped"
+    pub fn from_spec(spec: &str) -> Result<Self, String> {
+        let mut renderer = Renderer::default();
+        for option in spec.split(',').map(str::trim).filter(|o| !o.is_empty()) {
+            match option {
+                "ascii" => renderer.glyphs = Glyphs::Ascii,
+                "unicode" => renderer.glyphs = Glyphs::Unicode,
+                "numbers" => renderer.numbers = true,
+                "flipped" => renderer.flipped = true,
+                other => return Err(format!("unknown board style '{}' (expected ascii, unicode, numbers or flipped)", other)),
+            }
+        }
+        Ok(renderer)
+    }
+
+    // One three character cell for a dark square
+    fn cell(&self, board: &Board, index: usize) -> String {
+        let piece = board.squares[index];
+        let on_path = self.last_move.as_ref().is_some_and(|m| m.path.contains(index));
+        let captured = self.last_move.as_ref().is_some_and(|m| m.captures.contains(index));
+
+        if captured && piece.is_empty() {
+            return format!(" {} ", self.glyphs.captured());
+        }
+        if on_path {
+            return format!("[{}]", self.glyphs.piece(piece));
+        }
+        if self.numbers && piece.is_empty() {
+            return format!("{:^3}", index + 1);
+        }
+        format!(" {} ", self.glyphs.piece(piece))
+    }
+
+    pub fn render(&self, board: &Board) -> String {
+        let frame = self.glyphs.frame();
+        let size = board.rules().size();
+        let order: Vec<usize> = if self.flipped { (0..size).rev().collect() } else { (0..size).collect() };
+        // Room for the row numbers on the left
+        let margin = size.to_string().len();
+
+        let mut text = format!("{:margin$}  ", "");
+        for &col in &order {
+            text.push_str(&format!(" {} ", (b'A' + col as u8) as char));
+        }
+        text.truncate(text.trim_end().len());
+        text.push('\n');
+        text.push_str(&frame.line(frame.top, margin, size));
+
+        for &row in &order {
+            text.push_str(&format!("{:>margin$} {}", row + 1, frame.side));
+            for &col in &order {
+                match board.coords_to_index(row, col) {
+                    Some(index) => text.push_str(&self.cell(board, index)),
+                    None => text.push_str("   "),
+                }
+            }
+            text.push_str(&format!("{}\n", frame.side));
+        }
+
+        text.push_str(&frame.line(frame.bottom, margin, size));
+        text.push_str(&format!("{:?} to move  {}\n", board.turn, board.to_fen()));
+        text
+    }
+}
+
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // {:#} draws the Unicode diagram
+        let renderer = if f.alternate() { Renderer::unicode() } else { Renderer::ascii() };
+        write!(f, "{}", renderer.render(self))
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_numeric())
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+// A one line key to the pieces, e.g. "Red r (king R), Black b (king B)"
+pub fn legend(glyphs: Glyphs) -> String {
+    format!("Red {} (king {}), Black {} (king {})",
+            glyphs.piece(Piece::RedMan), glyphs.piece(Piece::RedKing), glyphs.piece(Piece::BlackMan), glyphs.piece(Piece::BlackKing))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Black king on 3 and man on 10, Red man on 18 and king on 30
+    fn board() -> Board {
+        Board::from_fen("B:W18,K30:B10,K3").unwrap()
+    }
+
+    #[test]
+    fn ascii_and_unicode_draw_the_same_board() {
+        assert_eq!(Renderer::ascii().render(&board()), concat!(
+            "    A  B  C  D  E  F  G  H\n",
+            "  +------------------------+\n",
+            "1 |    .     .     B     . |\n",
+            "2 | .     .     .     .    |\n",
+            "3 |    .     b     .     . |\n",
+            "4 | .     .     .     .    |\n",
+            "5 |    .     r     .     . |\n",
+            "6 | .     .     .     .    |\n",
+            "7 |    .     .     .     . |\n",
+            "8 | .     R     .     .    |\n",
+            "  +------------------------+\n",
+            "Black to move  B:W18,K30:BK3,10\n",
+        ));
+        assert_eq!(format!("{:#}", board()), concat!(
+            "    A  B  C  D  E  F  G  H\n",
+            "  ┌────────────────────────┐\n",
+            "1 │    ·     ·     ⛃     · │\n",
+            "2 │ ·     ·     ·     ·    │\n",
+            "3 │    ·     ⛂     ·     · │\n",
+            "4 │ ·     ·     ·     ·    │\n",
+            "5 │    ·     ⛀     ·     · │\n",
+            "6 │ ·     ·     ·     ·    │\n",
+            "7 │    ·     ·     ·     · │\n",
+            "8 │ ·     ⛁     ·     ·    │\n",
+            "  └────────────────────────┘\n",
+            "Black to move  B:W18,K30:BK3,10\n",
+        ));
+        assert_eq!(legend(Glyphs::Ascii), "Red r (king R), Black b (king B)");
+        assert_eq!(legend(Glyphs::Unicode), "Red ⛀ (king ⛁), Black ⛂ (king ⛃)");
+    }
+
+    #[test]
+    fn the_last_move_is_bracketed_and_its_capture_marked() {
+        let mut board = board();
+        board.make_move(&Move::parse("10-15", &board).unwrap()).unwrap();
+        let capture = Move::parse("18x11", &board).unwrap();
+        board.make_move(&capture).unwrap();
+
+        assert_eq!(Renderer::ascii().numbered().highlight(&capture).render(&board), concat!(
+            "    A  B  C  D  E  F  G  H\n",
+            "  +------------------------+\n",
+            "1 |    1     2     B     4 |\n",
+            "2 | 5     6     7     8    |\n",
+            "3 |    9    10    [r]   12 |\n",
+            "4 |13    14     x    16    |\n",
+            "5 |   17    [.]   19    20 |\n",
+            "6 |21    22    23    24    |\n",
+            "7 |   25    26    27    28 |\n",
+            "8 |29     R    31    32    |\n",
+            "  +------------------------+\n",
+            "Black to move  B:W11,K30:BK3\n",
+        ));
+        assert_eq!(Renderer::from_spec("unicode, flipped").unwrap().highlight(&capture).render(&board), concat!(
+            "    H  G  F  E  D  C  B  A\n",
+            "  ┌────────────────────────┐\n",
+            "8 │    ·     ·     ⛁     · │\n",
+            "7 │ ·     ·     ·     ·    │\n",
+            "6 │    ·     ·     ·     · │\n",
+            "5 │ ·     ·    [·]    ·    │\n",
+            "4 │    ·     ×     ·     · │\n",
+            "3 │ ·    [⛀]    ·     ·    │\n",
+            "2 │    ·     ·     ·     · │\n",
+            "1 │ ·     ⛃     ·     ·    │\n",
+            "  └────────────────────────┘\n",
+            "Black to move  B:W11,K30:BK3\n",
+        ));
+        assert!(Renderer::from_spec("ascii,sideways").is_err());
+    }
+}
@@ -0,0 +1,272 @@
+use crate::board::{Board, Color, Piece};
+use crate::eval_moves::MoveEvaluator;
+use crate::mv::{Move, crowning_square};
+
+// The rules of one game: board geometry, starting position, move generation,
+// promotion, capture obligations and the result. Every Board carries its
+// Variant and asks Variant::rules() for these, so the searches, the move
+// parser and the UIs never name a variant themselves.
+//
+// The defaults are American checkers; each other game overrides what it
+// does differently.
+pub trait Rules: Sync {
+    // Rows and columns of the board
+    fn size(&self) -> usize {
+        8
+    }
+
+    // Playable (dark) squares, numbered 1..=num_squares()
+    fn num_squares(&self) -> usize {
+        self.size() * self.size() / 2
+    }
+
+    // Mirrored boards have a light square in each player's left corner, so
+    // the dark squares of the top row are the even columns
+    fn mirrored(&self) -> bool {
+        false
+    }
+
+    // Rows of men each side starts with
+    fn rows_of_men(&self) -> usize {
+        3
+    }
+
+    // Black's men fill the top rows and Red's the bottom ones
+    fn setup(&self, squares: &mut [Piece]) {
+        let men = self.rows_of_men() * self.size() / 2;
+        let count = self.num_squares();
+        squares[..count].fill(Piece::Empty);
+        squares[..men].fill(Piece::BlackMan);
+        squares[count - men..count].fill(Piece::RedMan);
+    }
+
+    // Kings move and capture any distance along a free diagonal
+    fn flying_kings(&self) -> bool {
+        false
+    }
+
+    // Men may capture backward as well as forward
+    fn men_capture_backward(&self) -> bool {
+        false
+    }
+
+    // Men may jump kings
+    fn men_capture_kings(&self) -> bool {
+        true
+    }
+
+    // Append all legal moves for the side to move to `moves`
+    fn generate_moves(&self, board: &Board, moves: &mut Vec<Move>) {
+        MoveEvaluator::new(board).generate(moves)
+    }
+
+    // A man reaching the far row in the middle of a capture is crowned at
+    // once and finishes the capture as a king
+    fn promotes_mid_capture(&self) -> bool {
+        false
+    }
+
+    // Whether the piece that just played m (now standing on m.to) is crowned
+    fn promote(&self, board: &Board, m: &Move) -> bool {
+        let piece = board.squares[m.to];
+        if self.promotes_mid_capture() && !m.captures.is_empty() {
+            return m.path.iter().skip(1).any(|index| crowning_square(board, piece, index));
+        }
+        crowning_square(board, piece, m.to)
+    }
+
+    // How a capture ranks against the others available; only the highest
+    // ranked captures are legal. All equal means a free choice.
+    fn capture_rank(&self, _board: &Board, _m: &Move) -> CaptureRank {
+        [0; 4]
+    }
+
+    // Back-rank squares whose men form the bridge, for the eval's back_rank
+    // term. Only American checkers names them.
+    fn bridge_squares(&self, _color: Color) -> &'static [usize] {
+        &[]
+    }
+
+    // The dog-hole, as (square a man of `color` gets stuck on, square the
+    // opponent holds it from), for the eval's dog_hole term
+    fn dog_hole(&self, _color: Color) -> Option<(usize, usize)> {
+        None
+    }
+
+    // The aim is to run out of pieces or moves rather than to avoid it
+    fn reversed_goal(&self) -> bool {
+        false
+    }
+
+    // Who wins once `stuck` can't move, having no pieces left or being blocked
+    fn winner_when_stuck(&self, stuck: Color) -> Color {
+        if self.reversed_goal() { stuck } else { stuck.toggle() }
+    }
+}
+
+// Compared element by element, so earlier entries decide first
+pub type CaptureRank = [usize; 4];
+
+fn kings_taken(board: &Board, m: &Move) -> usize {
+    m.captures.iter().filter(|&c| board.squares[c].is_king()).count()
+}
+
+// 8x8, short kings, men capture forward only, any capture may be chosen
+pub struct American;
+
+impl Rules for American {
+    // Squares 30 and 32 for Red, 1 and 3 for Black
+    fn bridge_squares(&self, color: Color) -> &'static [usize] {
+        match color {
+            Color::Red => &[29, 31],
+            Color::Black => &[0, 2],
+        }
+    }
+
+    // A Red man on 5 held by Black on 1, or a Black man on 28 held by Red on 32
+    fn dog_hole(&self, color: Color) -> Option<(usize, usize)> {
+        match color {
+            Color::Red => Some((4, 0)),
+            Color::Black => Some((27, 31)),
+        }
+    }
+}
+
+// 10x10, flying kings, men capture backward, the capture taking the most
+// pieces is compulsory
+pub struct International;
+
+impl Rules for International {
+    fn size(&self) -> usize {
+        10
+    }
+
+    fn rows_of_men(&self) -> usize {
+        4
+    }
+
+    fn flying_kings(&self) -> bool {
+        true
+    }
+
+    fn men_capture_backward(&self) -> bool {
+        true
+    }
+
+    fn capture_rank(&self, _board: &Board, m: &Move) -> CaptureRank {
+        [m.captures.len(), 0, 0, 0]
+    }
+}
+
+// 8x8, flying kings, men capture backward, a man crowned during a capture
+// carries on jumping as a king, any capture may be chosen
+pub struct Russian;
+
+impl Rules for Russian {
+    fn flying_kings(&self) -> bool {
+        true
+    }
+
+    fn men_capture_backward(&self) -> bool {
+        true
+    }
+
+    fn promotes_mid_capture(&self) -> bool {
+        true
+    }
+}
+
+// International rules on the 8x8 board
+pub struct Brazilian;
+
+impl Rules for Brazilian {
+    fn flying_kings(&self) -> bool {
+        true
+    }
+
+    fn men_capture_backward(&self) -> bool {
+        true
+    }
+
+    fn capture_rank(&self, _board: &Board, m: &Move) -> CaptureRank {
+        [m.captures.len(), 0, 0, 0]
+    }
+}
+
+// 8x8 mirrored, short kings, men capture forward only and never take kings.
+// The capture taking the most pieces, then made by a king, then taking the
+// most kings, then meeting a king soonest, is compulsory.
+pub struct Italian;
+
+impl Rules for Italian {
+    fn mirrored(&self) -> bool {
+        true
+    }
+
+    fn men_capture_kings(&self) -> bool {
+        false
+    }
+
+    fn capture_rank(&self, board: &Board, m: &Move) -> CaptureRank {
+        let by_king = board.squares[m.from].is_king() as usize;
+        let first_king = m.captures.iter()
+            .position(|c| board.squares[c].is_king())
+            .map_or(0, |position| m.captures.len() - position);
+        [m.captures.len(), by_king, kings_taken(board, m), first_king]
+    }
+}
+
+// 8x8 mirrored, flying kings, men capture forward only. The capture taking
+// the most pieces, then the most kings, is compulsory.
+pub struct Spanish;
+
+impl Rules for Spanish {
+    fn mirrored(&self) -> bool {
+        true
+    }
+
+    fn flying_kings(&self) -> bool {
+        true
+    }
+
+    fn capture_rank(&self, board: &Board, m: &Move) -> CaptureRank {
+        [m.captures.len(), 0, kings_taken(board, m), 0]
+    }
+}
+
+// 8x8, flying kings, men capture backward, any capture may be chosen. A man
+// passing the far row during a capture stays a man unless it stops there.
+pub struct Pool;
+
+impl Rules for Pool {
+    fn flying_kings(&self) -> bool {
+        true
+    }
+
+    fn men_capture_backward(&self) -> bool {
+        true
+    }
+}
+
+// 8x8, flying kings, men capture forward only. When a king can capture it
+// must, otherwise any capture may be chosen.
+pub struct Czech;
+
+impl Rules for Czech {
+    fn flying_kings(&self) -> bool {
+        true
+    }
+
+    fn capture_rank(&self, board: &Board, m: &Move) -> CaptureRank {
+        [board.squares[m.from].is_king() as usize, 0, 0, 0]
+    }
+}
+
+// American moves, but a side wins by losing all its pieces or getting blocked
+pub struct Giveaway;
+
+impl Rules for Giveaway {
+    fn reversed_goal(&self) -> bool {
+        true
+    }
+}
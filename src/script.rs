@@ -0,0 +1,308 @@
+use std::fmt;
+use std::fs;
+use crate::board::{Board, Color, Piece};
+use crate::mv::Move;
+use crate::variant::{Variant, MAX_SQUARES};
+
+// Scripted games for regression scenarios. A script is a move list (plain
+// or PDN) plus expectations about the position at given plies:
+//
+//   [FEN "W:W21-32:B1-12"]        optional start position, also `fen <FEN>`
+//   [GameType "20"]               PDN game type, before the FEN (21 American,
+//                                 20 international, 25 Russian, 26 Brazilian,
+//                                 22 Italian, 24 Spanish)
+//   1. 22-18 11-15 2. 18x11 8x15  moves in either notation; move numbers,
+//                                 results and {comments} are skipped
+//   expect fen B:W...:B...        checked after the moves so far
+//   expect @4 turn red            checked after ply 4 (0 is the start)
+//   expect pieces red=11 black=12
+//   expect winner black
+//   expect board                  followed by 8 (10) rows, top row first, with
+//   -b-b-b-b                      either every square or just the dark ones
+//   ...                           per row: r b R B, anything else is empty
+//
+// `#` and `;` start a comment that runs to the end of the line.
+
+#[derive(Debug, Clone)]
+pub enum Check {
+    Fen(Board),
+    Squares([Piece; MAX_SQUARES]),
+    Pieces { red: u8, black: u8 },
+    Turn(Color),
+    Winner(Color),
+}
+
+#[derive(Debug, Clone)]
+pub struct Expectation {
+    pub ply: usize,
+    pub check: Check,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScriptMove {
+    pub text: String,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Script {
+    pub start: Board,
+    pub moves: Vec<ScriptMove>,
+    pub expectations: Vec<Expectation>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScriptError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+fn error<T>(line: usize, message: String) -> Result<T, ScriptError> {
+    Err(ScriptError { line, message })
+}
+
+fn parse_color(word: &str, line: usize) -> Result<Color, ScriptError> {
+    match word.to_lowercase().as_str() {
+        "red" | "white" | "w" => Ok(Color::Red),
+        "black" | "b" => Ok(Color::Black),
+        other => error(line, format!("unknown color '{}'", other)),
+    }
+}
+
+fn empty_squares() -> [Piece; MAX_SQUARES] {
+    [Piece::Empty; MAX_SQUARES]
+}
+
+// One row of a board diagram, as the pieces on its dark squares
+fn parse_row(text: &str, row: usize, line: usize, board: &Board) -> Result<Vec<Piece>, ScriptError> {
+    let size = board.rules().size();
+    let cells: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    let dark: Vec<char> = match cells.len() {
+        n if n == size / 2 => cells,
+        // Keep the columns the board has dark squares in on this row
+        n if n == size => cells.into_iter().skip(board.index_to_coords(row * size / 2).1).step_by(2).collect(),
+        n => return error(line, format!("board row {} has {} squares, expected {} or {}", row + 1, n, size / 2, size)),
+    };
+
+    Ok(dark.into_iter().map(Piece::from_char).collect())
+}
+
+fn parse_check(words: &[&str], lines: &mut dyn Iterator<Item = (usize, &str)>, line: usize, variant: Variant) -> Result<Check, ScriptError> {
+    match words {
+        ["fen", fen] => Board::from_variant_fen(variant, fen).map(Check::Fen).or_else(|e| error(line, e)),
+        ["turn", color] => Ok(Check::Turn(parse_color(color, line)?)),
+        ["winner", color] => Ok(Check::Winner(parse_color(color, line)?)),
+        ["pieces", counts @ ..] => {
+            let (mut red, mut black) = (None, None);
+            for count in counts {
+                let parsed = count.split_once('=').and_then(|(k, v)| Some((k, v.parse::<u8>().ok()?)));
+                match parsed {
+                    Some(("red", n)) => red = Some(n),
+                    Some(("black", n)) => black = Some(n),
+                    _ => return error(line, format!("expected red=N or black=N, got '{}'", count)),
+                }
+            }
+            match (red, black) {
+                (Some(red), Some(black)) => Ok(Check::Pieces { red, black }),
+                _ => error(line, "pieces needs both red=N and black=N".to_string()),
+            }
+        },
+        ["board"] => {
+            let board = Board::for_variant(variant);
+            let (size, per_row) = (board.rules().size(), board.rules().size() / 2);
+            let mut squares = empty_squares();
+            for row in 0..size {
+                let Some((row_line, text)) = lines.next() else {
+                    return error(line, format!("board diagram needs {} rows", size));
+                };
+                let pieces = parse_row(text, row, row_line, &board)?;
+                squares[row * per_row..(row + 1) * per_row].copy_from_slice(&pieces);
+            }
+            Ok(Check::Squares(squares))
+        },
+        _ => error(line, format!("unknown expectation '{}'", words.join(" "))),
+    }
+}
+
+fn strip_comments(text: &str) -> String {
+    // {...} comments may span lines, # and ; ones end with the line
+    let mut out = String::new();
+    let mut depth = 0;
+    for c in text.chars() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth > 0 => depth -= 1,
+            '\n' => out.push('\n'),
+            _ if depth == 0 => out.push(c),
+            _ => {},
+        }
+    }
+
+    out.lines()
+        .map(|line| line.split(['#', ';']).next().unwrap_or(""))
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+fn is_move_number(word: &str) -> bool {
+    let digits = word.trim_end_matches('.');
+    word.ends_with('.') && !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+}
+
+// Game results in PDN move text; 10x10 games count 2 for a win
+pub const RESULTS: [&str; 7] = ["1-0", "0-1", "1/2-1/2", "2-0", "0-2", "1-1", "*"];
+
+impl Script {
+    pub fn parse(text: &str) -> Result<Self, ScriptError> {
+        Self::parse_variant(Variant::American, text)
+    }
+
+    // Like parse, for a script whose rules aren't given by a GameType tag
+    pub fn parse_variant(variant: Variant, text: &str) -> Result<Self, ScriptError> {
+        let text = strip_comments(text);
+        let mut script = Script {
+            start: Board::for_variant(variant),
+            moves: Vec::new(),
+            expectations: Vec::new(),
+        };
+        let mut lines = text.lines().enumerate().map(|(i, line)| (i + 1, line));
+
+        while let Some((line, content)) = lines.next() {
+            let content = content.trim();
+
+            // PDN tag pairs: only the game type and FEN matter here
+            if let Some(tag) = content.strip_prefix('[') {
+                let value = |v: &str| v.trim_end_matches(']').trim().trim_matches('"').to_string();
+                if let Some(kind) = tag.strip_prefix("GameType ") {
+                    let kind = value(kind);
+                    let variant = Variant::from_pdn(&kind)
+                        .map_or_else(|| error(line, format!("unsupported game type '{}'", kind)), Ok)?;
+                    script.start = Board::for_variant(variant);
+                } else if let Some(fen) = tag.strip_prefix("FEN ") {
+                    script.start = Board::from_variant_fen(script.start.variant, &value(fen)).or_else(|e| error(line, e))?;
+                }
+                continue;
+            }
+
+            let words: Vec<&str> = content.split_whitespace().collect();
+            match words.as_slice() {
+                ["fen", fen] => {
+                    if !script.moves.is_empty() {
+                        return error(line, "the start position has to come before the moves".to_string());
+                    }
+                    script.start = Board::from_variant_fen(script.start.variant, fen).or_else(|e| error(line, e))?;
+                },
+                ["expect", rest @ ..] => {
+                    let (ply, rest) = match rest.first().and_then(|w| w.strip_prefix('@')) {
+                        Some(n) => match n.parse() {
+                            Ok(ply) => (ply, &rest[1..]),
+                            Err(_) => return error(line, format!("invalid ply '@{}'", n)),
+                        },
+                        None => (script.moves.len(), rest),
+                    };
+                    let check = parse_check(rest, &mut lines, line, script.start.variant)?;
+                    script.expectations.push(Expectation { ply, check, line });
+                },
+                _ => {
+                    let moves = words.iter()
+                        .filter(|w| !is_move_number(w) && !RESULTS.contains(w))
+                        // "1.22-18" is a move number glued to its move
+                        .map(|w| w.split_once('.').map(|(_, m)| m).unwrap_or(w))
+                        .map(|w| ScriptMove { text: w.to_string(), line });
+                    script.moves.extend(moves);
+                },
+            }
+        }
+
+        if let Some(late) = script.expectations.iter().find(|e| e.ply > script.moves.len()) {
+            return error(late.line, format!("ply {} is past the end of the script ({} moves)", late.ply, script.moves.len()));
+        }
+
+        Ok(script)
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("could not read '{}': {}", path, e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    // Play the script through the legal move generator, checking each
+    // expectation when its ply is reached. on_ply sees every position
+    // with the move that led to it (None for the start).
+    pub fn run<F>(&self, mut on_ply: F) -> Result<Board, ScriptError>
+    where
+        F: FnMut(usize, &Board, Option<&Move>),
+    {
+        let mut board = self.start.clone();
+        on_ply(0, &board, None);
+        self.check(0, &board)?;
+
+        for (i, scripted) in self.moves.iter().enumerate() {
+            let Some(m) = Move::parse(&scripted.text, &board) else {
+                let legal: Vec<String> = board.get_valid_moves().iter().map(|m| m.to_numeric()).collect();
+                let legal = if legal.is_empty() { "none".to_string() } else { legal.join(" ") };
+                return error(scripted.line, format!(
+                    "ply {}: {:?} can't play '{}' (legal moves: {})", i + 1, board.turn, scripted.text, legal
+                ));
+            };
+
+            board.make_move(&m).or_else(|e| error(scripted.line, e.to_string()))?;
+            on_ply(i + 1, &board, Some(&m));
+            self.check(i + 1, &board)?;
+        }
+
+        Ok(board)
+    }
+
+    fn check(&self, ply: usize, board: &Board) -> Result<(), ScriptError> {
+        for expectation in self.expectations.iter().filter(|e| e.ply == ply) {
+            let failed = |message: String| error(expectation.line, format!("after ply {}: {}", ply, message));
+
+            match &expectation.check {
+                Check::Fen(expected) => {
+                    if expected.squares != board.squares || expected.turn != board.turn {
+                        return failed(format!("expected FEN {}, got {}", expected.to_fen(), board.to_fen()));
+                    }
+                },
+                Check::Squares(expected) => {
+                    if expected != &board.squares {
+                        let mut diagram = Board::for_variant(board.variant);
+                        diagram.squares = *expected;
+                        return failed(format!("expected pieces {}, got {}",
+                                              diagram.to_fen().split_once(':').map(|(_, p)| p).unwrap_or(""),
+                                              board.to_fen().split_once(':').map(|(_, p)| p).unwrap_or("")));
+                    }
+                },
+                Check::Pieces { red, black } => {
+                    if (*red, *black) != (board.red_pieces, board.black_pieces) {
+                        return failed(format!("expected red={} black={}, got red={} black={}",
+                                              red, black, board.red_pieces, board.black_pieces));
+                    }
+                },
+                Check::Turn(color) => {
+                    if *color != board.turn {
+                        return failed(format!("expected {:?} to move, got {:?}", color, board.turn));
+                    }
+                },
+                Check::Winner(color) => {
+                    let winner = board.winner();
+                    if winner != Some(*color) {
+                        return failed(format!("expected {:?} to have won, got {}", color, match winner {
+                            Some(w) => format!("{:?} winning", w),
+                            None => "a game in progress".to_string(),
+                        }));
+                    }
+                },
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,156 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::evaluator::Score;
+use crate::mv::Move;
+
+// Shared control over a running search. Another thread can stop it, or move
+// its deadline (for example when a ponder search becomes the real one).
+#[derive(Debug, Clone, Default)]
+pub struct StopHandle {
+    stopped: Arc<AtomicBool>,
+    deadline: Arc<Mutex<Option<Instant>>>,
+}
+
+impl StopHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::SeqCst)
+    }
+
+    pub fn set_deadline(&self, deadline: Option<Instant>) {
+        if let Ok(mut slot) = self.deadline.lock() {
+            *slot = deadline;
+        }
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline.lock().ok().and_then(|slot| *slot)
+    }
+}
+
+// Limits for a search; a search stops at whichever limit it reaches first.
+// With no limits at all it runs to the default depth.
+#[derive(Debug, Clone, Default)]
+pub struct SearchLimits {
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub movetime: Option<Duration>,
+    pub infinite: bool,             // Only stop through the stop handle
+    pub stop: Option<StopHandle>,
+}
+
+impl SearchLimits {
+    pub const DEFAULT_DEPTH: u32 = 8;
+
+    pub fn depth(depth: u32) -> Self {
+        SearchLimits { depth: Some(depth), ..Default::default() }
+    }
+
+    pub fn nodes(nodes: u64) -> Self {
+        SearchLimits { nodes: Some(nodes), ..Default::default() }
+    }
+
+    pub fn movetime(movetime: Duration) -> Self {
+        SearchLimits { movetime: Some(movetime), ..Default::default() }
+    }
+
+    // Deepest iteration to start: the depth limit, unlimited if only
+    // nodes, time or the stop handle bound the search
+    pub fn max_depth(&self) -> u32 {
+        match (self.depth, self.nodes, self.movetime) {
+            (Some(depth), _, _) => depth,
+            _ if self.infinite => u32::MAX,
+            (None, None, None) => Self::DEFAULT_DEPTH,
+            _ => u32::MAX,
+        }
+    }
+
+    pub fn deadline(&self, start: Instant) -> Option<Instant> {
+        self.movetime.map(|t| start + t)
+    }
+
+    // Whether the search was stopped or ran out of time. The stop handle's
+    // deadline, when set, takes over from movetime.
+    pub fn expired(&self, start: Instant) -> bool {
+        let handle_deadline = self.stop.as_ref().and_then(StopHandle::deadline);
+
+        if self.stop.as_ref().is_some_and(StopHandle::is_stopped) {
+            return true;
+        }
+
+        match handle_deadline.or_else(|| self.deadline(start)) {
+            Some(deadline) => Instant::now() >= deadline,
+            None => false,
+        }
+    }
+}
+
+// What a search found, reported the same way by every engine
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    #[cfg_attr(feature = "serde", serde(with = "json::score"))]
+    pub score: Score,      // Relative to the side to move
+    pub depth: u32,        // Deepest completed iteration
+    pub nodes: u64,
+    pub pv: Vec<Move>,     // Expected line, starting with best_move
+    #[cfg_attr(feature = "serde", serde(rename = "time_ms", with = "json::millis"))]
+    pub elapsed: Duration,
+}
+
+// Field formats for SearchResult, the same ones the web API uses
+#[cfg(feature = "serde")]
+mod json {
+    // Infinite scores are wins and losses, which JSON numbers can't hold
+    pub mod score {
+        use serde::{Deserialize, Deserializer, Serializer};
+        use crate::evaluator::Score;
+
+        pub fn serialize<S: Serializer>(score: &Score, serializer: S) -> Result<S::Ok, S::Error> {
+            match *score {
+                s if s == f32::INFINITY => serializer.serialize_str("win"),
+                s if s == f32::NEG_INFINITY => serializer.serialize_str("loss"),
+                s => serializer.serialize_f32(s),
+            }
+        }
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Text {
+            Number(Score),
+            Word(String),
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Score, D::Error> {
+            match Text::deserialize(deserializer)? {
+                Text::Number(s) => Ok(s),
+                Text::Word(w) if w == "win" => Ok(f32::INFINITY),
+                Text::Word(w) if w == "loss" => Ok(f32::NEG_INFINITY),
+                Text::Word(w) => Err(serde::de::Error::custom(format!("expected a number, \"win\" or \"loss\", got \"{}\"", w))),
+            }
+        }
+    }
+
+    // Whole milliseconds
+    pub mod millis {
+        use std::time::Duration;
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(elapsed: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_u64(elapsed.as_millis() as u64)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+            u64::deserialize(deserializer).map(Duration::from_millis)
+        }
+    }
+}
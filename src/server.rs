@@ -0,0 +1,648 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use serde_json::{Value, json};
+use tungstenite::handshake::derive_accept_key;
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+use crate::board::{Board, Color};
+use crate::mv::{Move, Notation};
+use crate::player::{EnginePlayer, PlayerKind};
+use crate::search::{SearchLimits, SearchResult, StopHandle};
+use crate::variant::Variant;
+
+// A local web interface: the board page plus a JSON API over the same games.
+// Squares are numbered from 1 everywhere in the API (1-32 on 8x8 boards, 1-50
+// on international ones), and a game's state gives the board size and the
+// row and column of every square so the page can draw any variant.
+//
+//   GET  /                      the board page
+//   POST /api/games             new game, body {"variant": ..., "fen": ...} is optional
+//   GET  /api/games/ID          game state, including the legal moves
+//   GET  /api/games/ID/moves    just the legal moves
+//   POST /api/games/ID/moves    play {"move": "22-18"}, either notation
+//   POST /api/games/ID/engine   let an engine move, {"engine", "depth", "time"} are optional
+//   POST /api/games/ID/undo     take back the last move
+//   GET  /api/analysis          WebSocket: send {"fen", "variant"?, "depth"?, "time"?} to analyze a
+//                               position and {"stop": true} to stop; every iteration
+//                               comes back as {"type": "info", ...}, then {"type": "done", ...}
+
+pub const DEFAULT_PORT: u16 = 8080;
+
+const INDEX_HTML: &str = include_str!("web/index.html");
+const MAX_BODY: usize = 64 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct ServeConfig {
+    pub host: String,
+    pub port: u16,
+    pub engine: PlayerKind,
+    pub limits: SearchLimits,
+    pub hash_mb: usize,
+    pub notation: Notation,
+    pub start: Board,
+}
+
+impl Default for ServeConfig {
+    fn default() -> Self {
+        ServeConfig {
+            host: "127.0.0.1".to_string(),
+            port: DEFAULT_PORT,
+            engine: PlayerKind::AlphaBeta,
+            limits: SearchLimits::movetime(Duration::from_millis(1000)),
+            hash_mb: 64,
+            notation: Notation::Numeric,
+            start: Board::new(),
+        }
+    }
+}
+
+struct Game {
+    board: Board,
+    history: Vec<(Board, Move)>,  // Position before each move, for the move list and undo
+    engine: Arc<Mutex<EnginePlayer>>,
+}
+
+impl Game {
+    fn winner(&self) -> Option<Color> {
+        self.board.winner()
+    }
+}
+
+struct Shared {
+    config: ServeConfig,
+    games: Mutex<HashMap<u64, Game>>,
+    next_id: AtomicU64,
+}
+
+struct Request {
+    method: String,
+    path: String,
+    headers: HashMap<String, String>,  // Names lowercased
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+
+    fn json(&self) -> Result<Value, ApiError> {
+        if self.body.iter().all(u8::is_ascii_whitespace) {
+            return Ok(json!({}));
+        }
+        serde_json::from_slice(&self.body).map_err(|e| ApiError::bad_request(format!("invalid JSON: {}", e)))
+    }
+}
+
+#[derive(Debug)]
+struct ApiError {
+    status: u16,
+    message: String,
+}
+
+impl ApiError {
+    fn bad_request(message: String) -> Self {
+        ApiError { status: 400, message }
+    }
+
+    fn not_found(message: String) -> Self {
+        ApiError { status: 404, message }
+    }
+
+    fn conflict(message: String) -> Self {
+        ApiError { status: 409, message }
+    }
+}
+
+// With the serde feature, colors and moves are written by their Serialize
+// impls, so the API can't drift from the documented schema
+#[cfg(feature = "serde")]
+fn color_json(color: Color) -> Value {
+    serde_json::to_value(color).unwrap_or(Value::Null)
+}
+
+#[cfg(not(feature = "serde"))]
+fn color_json(color: Color) -> Value {
+    match color {
+        Color::Red => json!("red"),
+        Color::Black => json!("black"),
+    }
+}
+
+// Infinite scores are wins and losses, which JSON numbers can't hold
+fn score_json(score: f32) -> Value {
+    match score {
+        s if s == f32::INFINITY => json!("win"),
+        s if s == f32::NEG_INFINITY => json!("loss"),
+        // Rounded in f64, as f32 values pick up noise when widened; + 0.0 drops -0
+        s => json!(((s as f64) * 100.0).round() / 100.0 + 0.0),
+    }
+}
+
+#[cfg(feature = "serde")]
+fn move_fields(m: &Move) -> Value {
+    serde_json::to_value(m).unwrap_or(Value::Null)
+}
+
+#[cfg(not(feature = "serde"))]
+fn move_fields(m: &Move) -> Value {
+    json!({
+        "from": m.from + 1,
+        "to": m.to + 1,
+        "path": m.path.iter().map(|s| s + 1).collect::<Vec<usize>>(),
+        "captures": m.captures.iter().map(|s| s + 1).collect::<Vec<usize>>(),
+    })
+}
+
+// A move's fields plus its text in the game's notation
+fn move_json(m: &Move, board: &Board, notation: Notation) -> Value {
+    let mut json = move_fields(m);
+    json["text"] = json!(m.format(board, notation));
+    json
+}
+
+// A line of moves, each written on the board it is played from
+fn line_json(board: &Board, moves: &[Move], notation: Notation) -> Vec<String> {
+    let mut board = board.clone();
+    moves.iter()
+        .map(|m| {
+            let text = m.format(&board, notation);
+            let _ = board.make_move(m);
+            text
+        })
+        .collect()
+}
+
+fn search_json(board: &Board, result: &SearchResult, notation: Notation) -> Value {
+    json!({
+        "best": result.best_move.as_ref().map(|m| move_json(m, board, notation)),
+        "score": score_json(result.score),
+        "depth": result.depth,
+        "nodes": result.nodes,
+        "pv": line_json(board, &result.pv, notation),
+        "time_ms": result.elapsed.as_millis() as u64,
+    })
+}
+
+fn game_json(id: u64, game: &Game, notation: Notation) -> Value {
+    let board = &game.board;
+    let squares: Vec<String> = board.squares[..board.num_squares()].iter()
+        .map(|&p| if p.is_empty() { String::new() } else { p.to_char().to_string() })
+        .collect();
+    let legal: Vec<Value> = board.get_valid_moves().iter().map(|m| move_json(m, board, notation)).collect();
+
+    let coords: Vec<(usize, usize)> = (0..board.num_squares()).map(|index| board.index_to_coords(index)).collect();
+
+    json!({
+        "id": id,
+        "variant": board.variant.name(),
+        "size": board.rules().size(),
+        "coords": coords,
+        "fen": board.to_fen(),
+        "turn": color_json(board.turn),
+        "squares": squares,
+        "pieces": { "red": board.red_pieces, "black": board.black_pieces },
+        "moves": game.history.iter().map(|(before, m)| m.format(before, notation)).collect::<Vec<String>>(),
+        "last_move": game.history.last().map(|(before, m)| move_json(m, before, notation)),
+        "legal": legal,
+        "winner": game.winner().map(color_json),
+    })
+}
+
+// Engine kind and limits for one search: the body's fields over the server defaults
+fn engine_options(body: &Value, config: &ServeConfig) -> Result<(PlayerKind, SearchLimits), ApiError> {
+    let kind = match body.get("engine").and_then(Value::as_str) {
+        Some(name) => match PlayerKind::from_name(name) {
+            Some(PlayerKind::Human) | None => return Err(ApiError::bad_request(format!("unknown engine '{}'", name))),
+            Some(kind) => kind,
+        },
+        None => config.engine,
+    };
+
+    let depth = body.get("depth").and_then(Value::as_u64).map(|d| d as u32);
+    let movetime = body.get("time").and_then(Value::as_u64).map(Duration::from_millis);
+    let limits = match (depth, movetime) {
+        (None, None) => config.limits.clone(),
+        _ => SearchLimits { depth, movetime, ..Default::default() },
+    };
+
+    Ok((kind, limits))
+}
+
+// The "variant" field of a request, or `default` without one
+fn variant_option(body: &Value, default: Variant) -> Result<Variant, String> {
+    match body.get("variant").and_then(Value::as_str) {
+        Some(name) => Variant::from_name(name).ok_or_else(|| format!("unknown variant '{}'", name)),
+        None => Ok(default),
+    }
+}
+
+// The position a request names: its "fen" under its "variant", the start
+// position of that variant, or `default` when it names neither
+fn board_option(body: &Value, default: &Board) -> Result<Board, String> {
+    let variant = variant_option(body, default.variant)?;
+    match body.get("fen").and_then(Value::as_str) {
+        Some(fen) => Board::from_variant_fen(variant, fen),
+        None if variant != default.variant => Ok(Board::for_variant(variant)),
+        None => Ok(default.clone()),
+    }
+}
+
+fn game_id(text: &str) -> Result<u64, ApiError> {
+    text.parse().map_err(|_| ApiError::not_found(format!("no game '{}'", text)))
+}
+
+impl Shared {
+    fn with_game<T, F>(&self, id: u64, f: F) -> Result<T, ApiError>
+    where
+        F: FnOnce(&mut Game) -> Result<T, ApiError>,
+    {
+        let mut games = self.games.lock().unwrap();
+        let game = games.get_mut(&id).ok_or_else(|| ApiError::not_found(format!("no game {}", id)))?;
+        f(game)
+    }
+
+    fn new_game(&self, body: &Value) -> Result<Value, ApiError> {
+        let board = board_option(body, &self.config.start).map_err(ApiError::bad_request)?;
+        let engine = EnginePlayer::new(self.config.engine, self.config.limits.clone(), self.config.hash_mb);
+        let game = Game { board, history: Vec::new(), engine: Arc::new(Mutex::new(engine)) };
+
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let state = game_json(id, &game, self.config.notation);
+        self.games.lock().unwrap().insert(id, game);
+        Ok(state)
+    }
+
+    fn game(&self, id: u64) -> Result<Value, ApiError> {
+        self.with_game(id, |game| Ok(game_json(id, game, self.config.notation)))
+    }
+
+    fn legal_moves(&self, id: u64) -> Result<Value, ApiError> {
+        self.with_game(id, |game| {
+            let board = &game.board;
+            Ok(json!(board.get_valid_moves().iter().map(|m| move_json(m, board, self.config.notation)).collect::<Vec<Value>>()))
+        })
+    }
+
+    fn play(&self, id: u64, body: &Value) -> Result<Value, ApiError> {
+        let text = body.get("move").and_then(Value::as_str)
+            .ok_or_else(|| ApiError::bad_request("expected {\"move\": \"...\"}".to_string()))?;
+
+        self.with_game(id, |game| {
+            if game.winner().is_some() {
+                return Err(ApiError::conflict("the game is over".to_string()));
+            }
+            let m = Move::parse(text, &game.board)
+                .ok_or_else(|| ApiError::bad_request(format!("illegal move '{}'", text)))?;
+
+            let before = game.board.clone();
+            game.board.make_move(&m).map_err(|e| ApiError::bad_request(e.to_string()))?;
+            game.history.push((before, m));
+            Ok(game_json(id, game, self.config.notation))
+        })
+    }
+
+    // Search without holding the games lock, then play the move if the game
+    // is still where the search started
+    fn engine_move(&self, id: u64, body: &Value) -> Result<Value, ApiError> {
+        let (kind, limits) = engine_options(body, &self.config)?;
+        let (board, engine) = self.with_game(id, |game| {
+            if game.winner().is_some() {
+                return Err(ApiError::conflict("the game is over".to_string()));
+            }
+            Ok((game.board.clone(), game.engine.clone()))
+        })?;
+
+        let result = {
+            let mut engine = engine.lock().unwrap();
+            engine.kind = kind;
+            engine.limits = limits;
+            engine.choose(&board)
+        };
+        let m = result.best_move.clone()
+            .ok_or_else(|| ApiError::conflict("the engine found no move".to_string()))?;
+
+        self.with_game(id, |game| {
+            if game.board.hash() != board.hash() {
+                return Err(ApiError::conflict("the game changed during the search".to_string()));
+            }
+            game.board.make_move(&m).map_err(|e| ApiError::bad_request(e.to_string()))?;
+            game.history.push((board.clone(), m));
+            Ok(json!({
+                "search": search_json(&board, &result, self.config.notation),
+                "game": game_json(id, game, self.config.notation),
+            }))
+        })
+    }
+
+    fn undo(&self, id: u64) -> Result<Value, ApiError> {
+        self.with_game(id, |game| {
+            let (before, _) = game.history.pop()
+                .ok_or_else(|| ApiError::conflict("there is no move to take back".to_string()))?;
+            game.board = before;
+            Ok(game_json(id, game, self.config.notation))
+        })
+    }
+}
+
+// Read one request: request line, headers, then a Content-Length body
+fn read_request(reader: &mut BufReader<TcpStream>) -> io::Result<Option<Request>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "malformed request line"));
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+
+    let mut headers = HashMap::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let length: usize = headers.get("content-length").and_then(|l| l.parse().ok()).unwrap_or(0);
+    if length > MAX_BODY {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "request body too large"));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    Ok(Some(Request { method, path, headers, body }))
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        409 => "Conflict",
+        _ => "Internal Server Error",
+    }
+}
+
+fn respond(stream: &mut TcpStream, status: u16, content_type: &str, body: &[u8]) -> io::Result<()> {
+    write!(stream, "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+           status, reason(status), content_type, body.len())?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+fn respond_json(stream: &mut TcpStream, result: Result<Value, ApiError>) -> io::Result<()> {
+    let (status, body) = match result {
+        Ok(value) => (200, value),
+        Err(e) => (e.status, json!({ "error": e.message })),
+    };
+    respond(stream, status, "application/json", body.to_string().as_bytes())
+}
+
+fn handle_connection(shared: &Shared, stream: TcpStream) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let Some(request) = read_request(&mut reader)? else {
+        return Ok(());
+    };
+    let mut stream = stream;
+
+    let path = request.path.split('?').next().unwrap_or("");
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let is_upgrade = request.header("upgrade").is_some_and(|u| u.eq_ignore_ascii_case("websocket"));
+
+    let result = match (request.method.as_str(), segments.as_slice()) {
+        ("GET", []) => return respond(&mut stream, 200, "text/html; charset=utf-8", INDEX_HTML.as_bytes()),
+        ("GET", ["api", "analysis"]) if is_upgrade => {
+            // Whatever the reader buffered past the headers belongs to the socket
+            let leftover = reader.buffer().to_vec();
+            return serve_analysis(shared, &request, stream, leftover);
+        },
+        ("POST", ["api", "games"]) => request.json().and_then(|body| shared.new_game(&body)),
+        ("GET", ["api", "games", id]) => game_id(id).and_then(|id| shared.game(id)),
+        ("GET", ["api", "games", id, "moves"]) => game_id(id).and_then(|id| shared.legal_moves(id)),
+        ("POST", ["api", "games", id, "moves"]) => {
+            game_id(id).and_then(|id| request.json().and_then(|body| shared.play(id, &body)))
+        },
+        ("POST", ["api", "games", id, "engine"]) => {
+            game_id(id).and_then(|id| request.json().and_then(|body| shared.engine_move(id, &body)))
+        },
+        ("POST", ["api", "games", id, "undo"]) => game_id(id).and_then(|id| shared.undo(id)),
+        (method, _) => Err(ApiError::not_found(format!("no route for {} {}", method, path))),
+    };
+
+    respond_json(&mut stream, result)
+}
+
+// A running analysis, which hands its engine back when it finishes
+struct Analysis {
+    stop: StopHandle,
+    thread: JoinHandle<EnginePlayer>,
+}
+
+// Start analyzing the position in `command` with the connection's engine
+fn start_analysis(shared: &Shared, idle: &mut Option<EnginePlayer>, command: &Value, updates: mpsc::Sender<Value>) -> Result<Analysis, String> {
+    if command.get("fen").is_none() {
+        return Err("expected {\"fen\": ...} or {\"stop\": true}".to_string());
+    }
+    let board = board_option(command, &shared.config.start)?;
+    let (kind, limits) = engine_options(command, &shared.config).map_err(|e| e.message)?;
+
+    // Made on first use, and again if the last analysis panicked
+    let mut engine = idle.take()
+        .unwrap_or_else(|| EnginePlayer::new(shared.config.engine, SearchLimits::default(), shared.config.hash_mb));
+
+    // Without a depth or time the search runs until it is stopped, rather
+    // than for the server's default time
+    let stop = StopHandle::new();
+    let limits = if command.get("depth").is_none() && command.get("time").is_none() {
+        SearchLimits { infinite: true, ..Default::default() }
+    } else {
+        limits
+    };
+    engine.kind = kind;
+    engine.limits = SearchLimits { stop: Some(stop.clone()), ..limits };
+    let notation = shared.config.notation;
+
+    // Updates carry the FEN, so a client can drop those of an older analysis
+    let thread = thread::spawn(move || {
+        let fen = board.to_fen();
+        let result = engine.search(&board, |info| {
+            let mut update = search_json(&board, info, notation);
+            update["type"] = json!("info");
+            update["fen"] = json!(fen);
+            let _ = updates.send(update);
+        });
+        let mut update = search_json(&board, &result, notation);
+        update["type"] = json!("done");
+        update["fen"] = json!(fen);
+        let _ = updates.send(update);
+        engine
+    });
+
+    Ok(Analysis { stop, thread })
+}
+
+fn serve_analysis(shared: &Shared, request: &Request, mut stream: TcpStream, leftover: Vec<u8>) -> io::Result<()> {
+    let Some(key) = request.header("sec-websocket-key") else {
+        return respond_json(&mut stream, Err(ApiError::bad_request("missing Sec-WebSocket-Key".to_string())));
+    };
+    write!(stream, "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+           derive_accept_key(key.as_bytes()))?;
+
+    // A short read timeout lets one thread both read commands and forward updates
+    stream.set_read_timeout(Some(Duration::from_millis(50)))?;
+    let mut socket = WebSocket::from_partially_read(stream, leftover, Role::Server, None);
+    let (updates_tx, updates) = mpsc::channel::<Value>();
+    let mut idle: Option<EnginePlayer> = None;
+    let mut running: Option<Analysis> = None;
+
+    let stop = |running: &mut Option<Analysis>, idle: &mut Option<EnginePlayer>| {
+        if let Some(analysis) = running.take() {
+            analysis.stop.stop();
+            *idle = analysis.thread.join().ok();
+        }
+    };
+
+    loop {
+        while let Ok(update) = updates.try_recv() {
+            if socket.send(Message::Text(update.to_string())).is_err() {
+                stop(&mut running, &mut idle);
+                return Ok(());
+            }
+        }
+
+        match socket.read() {
+            Ok(Message::Text(text)) => {
+                stop(&mut running, &mut idle);
+                let command: Value = match serde_json::from_str(&text) {
+                    Ok(command) => command,
+                    Err(e) => {
+                        let _ = socket.send(Message::Text(json!({ "type": "error", "error": e.to_string() }).to_string()));
+                        continue;
+                    },
+                };
+                if command.get("stop").is_some() {
+                    continue;
+                }
+
+                match start_analysis(shared, &mut idle, &command, updates_tx.clone()) {
+                    Ok(analysis) => running = Some(analysis),
+                    Err(error) => {
+                        let _ = socket.send(Message::Text(json!({ "type": "error", "error": error }).to_string()));
+                    },
+                }
+            },
+            Ok(Message::Close(_)) => break,
+            Ok(_) => {},
+            Err(tungstenite::Error::Io(e)) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {},
+            Err(_) => break,
+        }
+    }
+
+    stop(&mut running, &mut idle);
+    Ok(())
+}
+
+// Serve until the process is killed, one thread per connection
+pub fn run(config: ServeConfig) -> Result<(), String> {
+    let listener = TcpListener::bind((config.host.as_str(), config.port))
+        .map_err(|e| format!("could not listen on {}:{}: {}", config.host, config.port, e))?;
+    println!("Serving on http://{}:{}/", config.host, config.port);
+
+    let shared = Arc::new(Shared {
+        config,
+        games: Mutex::new(HashMap::new()),
+        next_id: AtomicU64::new(1),
+    });
+
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let shared = shared.clone();
+        thread::spawn(move || {
+            if let Err(e) = handle_connection(&shared, stream) {
+                eprintln!("Connection error: {}", e);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shared() -> Shared {
+        Shared { config: ServeConfig::default(), games: Mutex::new(HashMap::new()), next_id: AtomicU64::new(1) }
+    }
+
+    #[test]
+    fn games_can_use_any_variant() {
+        let shared = shared();
+
+        let american = shared.new_game(&json!({})).unwrap();
+        assert_eq!((american["variant"].as_str(), american["size"].as_u64()), (Some("american"), Some(8)));
+        assert_eq!(american["coords"][0], json!([0, 1]));
+
+        let international = shared.new_game(&json!({ "variant": "international" })).unwrap();
+        assert_eq!(international["size"], json!(10));
+        assert_eq!(international["squares"].as_array().map(Vec::len), Some(50));
+        assert_eq!(international["coords"][49], json!([9, 8]));
+
+        let id = international["id"].as_u64().unwrap();
+        let state = shared.play(id, &json!({ "move": "32-28" })).unwrap();
+        assert_eq!(state["turn"], json!("black"));
+        assert_eq!(state["moves"], json!(["32-28"]));
+
+        let position = shared.new_game(&json!({ "variant": "russian", "fen": "W:W21:B12" })).unwrap();
+        assert_eq!(position["variant"], json!("russian"));
+        assert!(shared.new_game(&json!({ "variant": "chess" })).is_err());
+    }
+
+    // The API's move and search fields must read the same as the serde schema
+    #[cfg(feature = "serde")]
+    #[test]
+    fn api_json_matches_the_serde_schema() {
+        let board = Board::new();
+        for m in board.get_valid_moves() {
+            let mut api = move_json(&m, &board, Notation::Numeric);
+            assert!(api["text"].is_string());
+            api.as_object_mut().unwrap().remove("text");
+            assert_eq!(api, serde_json::to_value(&m).unwrap());
+        }
+
+        for score in [f32::INFINITY, f32::NEG_INFINITY, 0.25, -1.5, 0.0] {
+            let result = SearchResult {
+                best_move: None,
+                score,
+                depth: 8,
+                nodes: 5741,
+                pv: Vec::new(),
+                elapsed: Duration::from_millis(12),
+            };
+            let api = search_json(&board, &result, Notation::Numeric);
+            let schema = serde_json::to_value(&result).unwrap();
+            for field in ["score", "depth", "nodes", "time_ms"] {
+                assert_eq!(api[field], schema[field], "{} for score {}", field, score);
+            }
+        }
+
+        assert_eq!(color_json(Color::Red), json!("red"));
+        assert_eq!(color_json(Color::Black), json!("black"));
+    }
+}
@@ -0,0 +1,70 @@
+use crate::evaluator::Score;
+use crate::mv::Move;
+
+// What a stored score says about the true value of the position
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bound {
+    Exact,
+    Lower,  // The search failed high: the value is at least score
+    Upper,  // The search failed low: the value is at most score
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub key: u64,
+    pub depth: u32,
+    pub score: Score,
+    pub bound: Bound,
+    pub best_move: Option<Move>,
+}
+
+// Fixed size hash table of search results indexed by Zobrist key. A table
+// of size 0 stores nothing.
+#[derive(Debug, Clone, Default)]
+pub struct TranspositionTable {
+    entries: Vec<Option<Entry>>,
+}
+
+impl TranspositionTable {
+    // Rough bytes per slot, counting a typical short move path
+    const ENTRY_BYTES: usize = std::mem::size_of::<Option<Entry>>() + 32;
+
+    pub fn new(megabytes: usize) -> Self {
+        let count = megabytes * 1024 * 1024 / Self::ENTRY_BYTES;
+        TranspositionTable { entries: vec![None; count] }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|e| *e = None);
+    }
+
+    fn slot(&self, key: u64) -> usize {
+        (key % self.entries.len() as u64) as usize
+    }
+
+    pub fn probe(&self, key: u64) -> Option<&Entry> {
+        if !self.is_enabled() {
+            return None;
+        }
+        self.entries[self.slot(key)].as_ref().filter(|e| e.key == key)
+    }
+
+    // Depth-preferred replacement, but always replace entries of other positions
+    pub fn store(&mut self, entry: Entry) {
+        if !self.is_enabled() {
+            return;
+        }
+        let slot = self.slot(entry.key);
+        let replace = match &self.entries[slot] {
+            Some(old) => old.key != entry.key || entry.depth >= old.depth,
+            None => true,
+        };
+        if replace {
+            self.entries[slot] = Some(entry);
+        }
+    }
+}
@@ -0,0 +1,545 @@
+use std::io::{self, Write};
+use std::sync::mpsc::{self, Receiver};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use crossterm::{cursor, execute, queue, terminal};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::style::{self, Attribute, Color as TermColor, Print, SetAttribute, SetBackgroundColor, SetForegroundColor};
+use crate::board::{Board, Color, Piece};
+use crate::mv::{Move, Notation};
+use crate::player::{EnginePlayer, PlayerKind};
+use crate::search::{SearchLimits, SearchResult, StopHandle};
+
+// Full-screen terminal UI: a colored board with cursor-based move entry,
+// and side panels for the clocks, engine analysis and move history.
+//
+// A move is entered square by square: select a piece, then each landing
+// square in turn, so multi-jumps with several possible paths are entered
+// unambiguously. The move is played as soon as the squares picked so far
+// match exactly one legal move.
+
+#[derive(Debug, Clone)]
+pub struct TuiConfig {
+    pub red: PlayerKind,
+    pub black: PlayerKind,
+    pub limits: SearchLimits,
+    pub hash_mb: usize,
+    pub start: Board,
+    pub notation: Notation,
+    pub ponder: bool,
+}
+
+const SQUARE_W: u16 = 5;
+const SQUARE_H: u16 = 2;
+const BOARD_X: u16 = 3;   // Room for the row labels
+const BOARD_Y: u16 = 2;   // Title and column labels
+const PANEL_W: usize = 36;
+const ANIMATION_STEP: Duration = Duration::from_millis(220);
+
+const LIGHT: TermColor = TermColor::Rgb { r: 233, g: 214, b: 176 };
+const DARK: TermColor = TermColor::Rgb { r: 118, g: 84, b: 58 };
+const CURSOR: TermColor = TermColor::Rgb { r: 214, g: 180, b: 40 };
+const SELECTED: TermColor = TermColor::Rgb { r: 70, g: 150, b: 70 };
+const TARGET: TermColor = TermColor::Rgb { r: 60, g: 110, b: 170 };
+const LAST_MOVE: TermColor = TermColor::Rgb { r: 150, g: 110, b: 60 };
+const RED_PIECE: TermColor = TermColor::Rgb { r: 225, g: 40, b: 40 };
+const BLACK_PIECE: TermColor = TermColor::Rgb { r: 15, g: 15, b: 15 };
+const DIM: TermColor = TermColor::Rgb { r: 190, g: 160, b: 120 };
+
+// Restores the terminal however the UI exits
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide, terminal::Clear(terminal::ClearType::All))?;
+        Ok(TerminalGuard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+// An engine searching in the background. It owns the player until the
+// search is done and hands it back with the result.
+struct Thinking {
+    color: Color,
+    stop: StopHandle,
+    updates: Receiver<SearchResult>,
+    thread: JoinHandle<(EnginePlayer, SearchResult)>,
+}
+
+fn color_index(color: Color) -> usize {
+    match color {
+        Color::Red => 0,
+        Color::Black => 1,
+    }
+}
+
+fn format_clock(time: Duration) -> String {
+    let seconds = time.as_secs();
+    format!("{:02}:{:02}", seconds / 60, seconds % 60)
+}
+
+fn format_score(score: f32) -> String {
+    match score {
+        s if s == f32::INFINITY => "win".to_string(),
+        s if s == f32::NEG_INFINITY => "loss".to_string(),
+        s => format!("{:+.2}", s),
+    }
+}
+
+struct App {
+    config: TuiConfig,
+    board: Board,
+    history: Vec<(Board, Move)>,       // Position before each move, and the move
+    players: [Option<EnginePlayer>; 2],
+    kinds: [PlayerKind; 2],
+    thinking: Option<Thinking>,
+    analysis: Option<(Board, SearchResult)>,
+    cursor: (usize, usize),            // Board row and column
+    selection: Vec<usize>,             // Squares picked so far for the next move
+    clocks: [Duration; 2],
+    turn_started: Instant,
+    flipped: bool,                     // Black at the bottom
+    message: String,
+}
+
+impl App {
+    fn new(config: TuiConfig) -> Self {
+        let kinds = [config.red, config.black];
+        let player = |kind: PlayerKind| match kind {
+            PlayerKind::Human => None,
+            kind => Some(EnginePlayer::new(kind, config.limits.clone(), config.hash_mb).with_ponder(config.ponder)),
+        };
+
+        App {
+            board: config.start.clone(),
+            players: [player(kinds[0]), player(kinds[1])],
+            kinds,
+            history: Vec::new(),
+            thinking: None,
+            analysis: None,
+            cursor: (config.start.rules().size() - 3, 0),
+            selection: Vec::new(),
+            clocks: [Duration::ZERO; 2],
+            turn_started: Instant::now(),
+            // Humans see their own side at the bottom
+            flipped: kinds[1] == PlayerKind::Human && kinds[0] != PlayerKind::Human,
+            message: String::new(),
+            config,
+        }
+    }
+
+    fn legal_moves(&self) -> Vec<Move> {
+        if self.board.is_game_over() { Vec::new() } else { self.board.get_valid_moves() }
+    }
+
+    fn winner(&self) -> Option<Color> {
+        self.board.winner()
+    }
+
+    fn human_to_move(&self) -> bool {
+        self.kinds[color_index(self.board.turn)] == PlayerKind::Human
+    }
+
+    // Moves that start with the squares selected so far
+    fn matching_moves(&self) -> Vec<Move> {
+        self.legal_moves()
+            .into_iter()
+            .filter(|m| m.path.starts_with(&self.selection))
+            .collect()
+    }
+
+    // Squares the cursor can pick next
+    fn targets(&self) -> Vec<usize> {
+        let depth = self.selection.len();
+        let mut targets: Vec<usize> = self.matching_moves()
+            .iter()
+            .filter_map(|m| m.path.get(depth))
+            .collect();
+        targets.sort_unstable();
+        targets.dedup();
+        targets
+    }
+
+    fn clock(&self, color: Color) -> Duration {
+        let mut time = self.clocks[color_index(color)];
+        if self.board.turn == color && self.winner().is_none() {
+            time += self.turn_started.elapsed();
+        }
+        time
+    }
+
+    // Rows and columns of the board being played
+    fn size(&self) -> usize {
+        self.board.rules().size()
+    }
+
+    fn screen_position(&self, row: usize, col: usize) -> (u16, u16) {
+        let last = self.size() - 1;
+        let (r, c) = if self.flipped { (last - row, last - col) } else { (row, col) };
+        (BOARD_X + c as u16 * SQUARE_W, BOARD_Y + r as u16 * SQUARE_H)
+    }
+
+    fn move_cursor(&mut self, screen_dr: i32, screen_dc: i32) {
+        let (dr, dc) = if self.flipped { (-screen_dr, -screen_dc) } else { (screen_dr, screen_dc) };
+        let last = self.size() as i32 - 1;
+        let row = (self.cursor.0 as i32 + dr).clamp(0, last);
+        let col = (self.cursor.1 as i32 + dc).clamp(0, last);
+        self.cursor = (row as usize, col as usize);
+    }
+
+    fn draw_board(&self, out: &mut impl Write, board: &Board, highlights: bool) -> io::Result<()> {
+        let last: Vec<usize> = self.history.last().map(|(_, m)| m.path.to_vec()).unwrap_or_default();
+        let targets = if highlights && self.human_to_move() { self.targets() } else { Vec::new() };
+
+        // Column and row labels follow the algebraic notation: A-H, 1-8
+        // (A-J, 1-10 on the big board)
+        for col in 0..self.size() {
+            let (x, _) = self.screen_position(0, col);
+            queue!(out, cursor::MoveTo(x + SQUARE_W / 2, BOARD_Y - 1), Print((b'A' + col as u8) as char))?;
+        }
+        for row in 0..self.size() {
+            let (_, y) = self.screen_position(row, 0);
+            queue!(out, cursor::MoveTo(0, y), Print(format!("{:>2}", row + 1)))?;
+        }
+
+        for row in 0..self.size() {
+            for col in 0..self.size() {
+                let (x, y) = self.screen_position(row, col);
+                let index = board.coords_to_index(row, col);
+
+                let background = match index {
+                    None => LIGHT,
+                    Some(_) if highlights && self.cursor == (row, col) => CURSOR,
+                    Some(i) if highlights && self.selection.contains(&i) => SELECTED,
+                    Some(i) if targets.contains(&i) => TARGET,
+                    Some(i) if last.contains(&i) => LAST_MOVE,
+                    Some(_) => DARK,
+                };
+
+                let (glyph, color) = match index.map(|i| board.squares[i]) {
+                    Some(piece) => match piece.color() {
+                        Some(owner) => (
+                            if piece.is_king() { "♛" } else { "●" },
+                            if owner == Color::Red { RED_PIECE } else { BLACK_PIECE },
+                        ),
+                        None => (" ", DIM),
+                    },
+                    None => (" ", DIM),
+                };
+
+                // Top line holds the piece, the bottom one the square number
+                let number = index.map(|i| format!("{:>4} ", i + 1)).unwrap_or_else(|| " ".repeat(SQUARE_W as usize));
+                queue!(
+                    out,
+                    SetBackgroundColor(background),
+                    cursor::MoveTo(x, y),
+                    SetForegroundColor(color),
+                    SetAttribute(Attribute::Bold),
+                    Print(format!("  {}  ", glyph)),
+                    SetAttribute(Attribute::Reset),
+                    SetBackgroundColor(background),
+                    cursor::MoveTo(x, y + 1),
+                    SetForegroundColor(DIM),
+                    Print(number),
+                    style::ResetColor,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn panel_lines(&self) -> Vec<String> {
+        let notation = self.config.notation;
+        let mut lines = Vec::new();
+        let marker = |color: Color| if self.board.turn == color && self.winner().is_none() { "▶" } else { " " };
+
+        lines.push(self.board.variant.title().to_string());
+        lines.push(String::new());
+        lines.push(format!("{} Red    {:<10} {}", marker(Color::Red), self.kinds[0].name(), format_clock(self.clock(Color::Red))));
+        lines.push(format!("{} Black  {:<10} {}", marker(Color::Black), self.kinds[1].name(), format_clock(self.clock(Color::Black))));
+        lines.push(format!("  Pieces: red {}, black {}", self.board.red_pieces, self.board.black_pieces));
+        lines.push(String::new());
+
+        let status = match (self.winner(), &self.thinking) {
+            (Some(winner), _) => format!("{:?} wins! (n: new game, q: quit)", winner),
+            (None, Some(thinking)) => format!("{:?} is thinking...", thinking.color),
+            (None, None) if !self.selection.is_empty() => "Pick the next square".to_string(),
+            (None, None) => format!("{:?} to move", self.board.turn),
+        };
+        lines.push(status);
+        lines.push(self.message.clone());
+        lines.push(String::new());
+
+        lines.push("Analysis".to_string());
+        match &self.analysis {
+            Some((board, result)) => {
+                lines.push(format!("  depth {}  score {}  nodes {}", result.depth, format_score(result.score), result.nodes));
+                let mut line_board = board.clone();
+                let pv: Vec<String> = result.pv.iter()
+                    .map(|m| {
+                        let text = m.format(&line_board, notation);
+                        let _ = line_board.make_move(m);
+                        text
+                    })
+                    .collect();
+                lines.push(format!("  pv {}", pv.join(" ")));
+            },
+            None => {
+                lines.push("  -".to_string());
+                lines.push(String::new());
+            },
+        }
+        lines.push(String::new());
+
+        // Move history, one move number per line, latest at the bottom
+        lines.push("Moves".to_string());
+        let mut numbered: Vec<String> = Vec::new();
+        for (ply, (before, m)) in self.history.iter().enumerate() {
+            let text = m.format(before, notation);
+            if ply % 2 == 0 {
+                numbered.push(format!("  {:>3}. {:<12}", ply / 2 + 1, text));
+            } else if let Some(line) = numbered.last_mut() {
+                line.push_str(&text);
+            }
+        }
+        let room = 10;
+        lines.extend(numbered.iter().skip(numbered.len().saturating_sub(room)).cloned());
+        lines
+    }
+
+    fn draw(&self, board: &Board, highlights: bool) -> io::Result<()> {
+        let mut out = io::stdout();
+        self.draw_board(&mut out, board, highlights)?;
+
+        let lines = self.panel_lines();
+        let (_, height) = terminal::size().unwrap_or((80, 24));
+        let panel_rows = height.saturating_sub(2).max(lines.len() as u16);
+        for row in 0..panel_rows {
+            let text: String = lines.get(row as usize).map(String::as_str).unwrap_or("").chars().take(PANEL_W).collect();
+            queue!(out, cursor::MoveTo(BOARD_X + self.size() as u16 * SQUARE_W + 3, row), Print(format!("{:<width$}", text, width = PANEL_W)))?;
+        }
+
+        let help = "arrows move  enter/space pick  esc cancel  u undo  f flip  n new  q quit";
+        queue!(out, cursor::MoveTo(0, BOARD_Y + self.size() as u16 * SQUARE_H + 1), SetForegroundColor(DIM), Print(help), style::ResetColor)?;
+        out.flush()
+    }
+
+    // Show a move one jump at a time, removing each captured piece as it's jumped
+    fn animate(&self, before: &Board, m: &Move) -> io::Result<()> {
+        if m.path.len() <= 2 {
+            return Ok(());
+        }
+
+        let piece = before.squares[m.from];
+        let mut frame = before.clone();
+        for (step, (from, to)) in m.path.iter().zip(m.path.iter().skip(1)).enumerate() {
+            frame.squares[from] = Piece::Empty;
+            frame.squares[to] = piece;
+            if let Some(captured) = m.captures.get(step) {
+                frame.squares[captured] = Piece::Empty;
+            }
+            self.draw(&frame, false)?;
+            thread::sleep(ANIMATION_STEP);
+        }
+        Ok(())
+    }
+
+    fn play(&mut self, m: Move) -> io::Result<()> {
+        let before = self.board.clone();
+        self.animate(&before, &m)?;
+
+        if self.board.make_move(&m).is_err() {
+            self.message = format!("Could not play {}", m.to_numeric());
+            return Ok(());
+        }
+
+        let mover = color_index(before.turn);
+        self.clocks[mover] += self.turn_started.elapsed();
+        self.turn_started = Instant::now();
+        self.history.push((before, m));
+        self.selection.clear();
+        Ok(())
+    }
+
+    fn start_engine(&mut self) {
+        if self.thinking.is_some() || self.winner().is_some() || self.human_to_move() {
+            return;
+        }
+
+        let color = self.board.turn;
+        let Some(mut player) = self.players[color_index(color)].take() else {
+            return;
+        };
+
+        let stop = StopHandle::new();
+        player.limits.stop = Some(stop.clone());
+        let (sender, updates) = mpsc::channel();
+        let board = self.board.clone();
+
+        let thread = thread::spawn(move || {
+            let result = player.search(&board, |result| {
+                let _ = sender.send(result.clone());
+            });
+            (player, result)
+        });
+
+        self.thinking = Some(Thinking { color, stop, updates, thread });
+    }
+
+    // Pick up analysis updates and the engine's move once it's done
+    fn poll_engine(&mut self) -> io::Result<()> {
+        let Some(thinking) = &self.thinking else {
+            return Ok(());
+        };
+
+        while let Ok(result) = thinking.updates.try_recv() {
+            self.analysis = Some((self.board.clone(), result));
+        }
+        if !thinking.thread.is_finished() {
+            return Ok(());
+        }
+
+        let thinking = self.thinking.take().expect("checked above");
+        let color = thinking.color;
+        match thinking.thread.join() {
+            Ok((player, result)) => {
+                self.players[color_index(color)] = Some(player);
+                self.analysis = Some((self.board.clone(), result.clone()));
+                match result.best_move {
+                    Some(m) => self.play(m)?,
+                    None => self.message = format!("{:?} found no move", color),
+                }
+            },
+            Err(_) => self.message = "The engine stopped unexpectedly".to_string(),
+        }
+        Ok(())
+    }
+
+    fn stop_engine(&mut self) {
+        if let Some(thinking) = self.thinking.take() {
+            thinking.stop.stop();
+            if let Ok((player, _)) = thinking.thread.join() {
+                self.players[color_index(thinking.color)] = Some(player);
+            }
+        }
+    }
+
+    fn pick(&mut self) -> io::Result<()> {
+        if !self.human_to_move() || self.winner().is_some() {
+            return Ok(());
+        }
+
+        let Some(index) = self.board.coords_to_index(self.cursor.0, self.cursor.1) else {
+            return Ok(());
+        };
+
+        // Picking another of your own pieces starts over from that piece
+        if self.selection.len() == 1 && index != self.selection[0]
+            && self.board.squares[index].color() == Some(self.board.turn) {
+            self.selection.clear();
+        }
+
+        if !self.targets().contains(&index) {
+            self.message = if self.selection.is_empty() {
+                "That piece has no legal move".to_string()
+            } else {
+                "Not a legal square for this move".to_string()
+            };
+            return Ok(());
+        }
+
+        self.selection.push(index);
+        self.message.clear();
+
+        let matching = self.matching_moves();
+        let complete: Vec<&Move> = matching.iter().filter(|m| m.path == self.selection[..]).collect();
+        if let ([m], 1) = (complete.as_slice(), matching.len()) {
+            let m = (*m).clone();
+            self.play(m)?;
+        }
+        Ok(())
+    }
+
+    fn undo(&mut self) {
+        if self.thinking.is_some() {
+            return;
+        }
+
+        // Go back to the last position where a human was to move
+        while let Some((before, _)) = self.history.pop() {
+            self.board = before;
+            if self.human_to_move() {
+                break;
+            }
+        }
+        self.selection.clear();
+        self.analysis = None;
+        self.turn_started = Instant::now();
+    }
+
+    fn new_game(&mut self) {
+        self.stop_engine();
+        self.board = self.config.start.clone();
+        self.history.clear();
+        self.selection.clear();
+        self.analysis = None;
+        self.clocks = [Duration::ZERO; 2];
+        self.turn_started = Instant::now();
+        self.message.clear();
+        for player in self.players.iter_mut().flatten() {
+            player.new_game();
+        }
+    }
+
+    // Returns false when the user quits
+    fn handle_key(&mut self, code: KeyCode) -> io::Result<bool> {
+        match code {
+            KeyCode::Char('q') | KeyCode::Char('Q') => return Ok(false),
+            KeyCode::Up => self.move_cursor(-1, 0),
+            KeyCode::Down => self.move_cursor(1, 0),
+            KeyCode::Left => self.move_cursor(0, -1),
+            KeyCode::Right => self.move_cursor(0, 1),
+            KeyCode::Enter | KeyCode::Char(' ') => self.pick()?,
+            KeyCode::Esc => self.selection.clear(),
+            KeyCode::Char('u') => self.undo(),
+            KeyCode::Char('n') => self.new_game(),
+            KeyCode::Char('f') => self.flipped = !self.flipped,
+            _ => {},
+        }
+        Ok(true)
+    }
+}
+
+pub fn run(config: TuiConfig) -> io::Result<()> {
+    let _guard = TerminalGuard::enter()?;
+    let mut app = App::new(config);
+
+    loop {
+        app.poll_engine()?;
+        app.start_engine();
+        app.draw(&app.board.clone(), true)?;
+
+        if event::poll(Duration::from_millis(100))? {
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => {
+                    let keep_playing = app.handle_key(key.code)?;
+                    if !keep_playing {
+                        break;
+                    }
+                },
+                Event::Resize(_, _) => execute!(io::stdout(), terminal::Clear(terminal::ClearType::All))?,
+                _ => {},
+            }
+        }
+    }
+
+    app.stop_engine();
+    Ok(())
+}
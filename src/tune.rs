@@ -0,0 +1,278 @@
+use rayon::prelude::*;
+use std::fs;
+use crate::ab_ai::bar_with;
+use crate::board::Board;
+use crate::script::{self, Script};
+use crate::variant::Variant;
+use crate::weights::EvalWeights;
+
+// A position together with the final result of the game it came from,
+// from Red's point of view: 1.0 win, 0.5 draw, 0.0 loss
+#[derive(Debug, Clone)]
+pub struct LabelledPosition {
+    pub board: Board,
+    pub result: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct TuneConfig {
+    pub step: f32,            // Amount each weight is nudged per attempt
+    pub max_iterations: u32,  // Passes over all weights before giving up
+}
+
+// Reported once before the first pass (iteration 0) and after every pass
+#[derive(Debug, Clone, Copy)]
+pub struct TuneProgress {
+    pub iteration: u32,
+    pub positions: usize,
+    pub k: f32,
+    pub error: f32,
+}
+
+impl Default for TuneConfig {
+    fn default() -> Self {
+        TuneConfig {
+            step: 0.05,
+            max_iterations: 100,
+        }
+    }
+}
+
+// A game result as PDN writes it, from Red's (White's) point of view
+fn pdn_result(word: &str) -> Option<f32> {
+    match word {
+        "1-0" | "2-0" => Some(1.0),
+        "0-1" | "0-2" => Some(0.0),
+        "1/2-1/2" | "1-1" => Some(0.5),
+        _ => None,
+    }
+}
+
+// Result field of a dataset line: "1-0", "0-1", "1/2-1/2" or a number
+fn parse_result(field: &str) -> Option<f32> {
+    match field.trim() {
+        "draw" => Some(0.5),
+        other => pdn_result(other).or_else(|| other.parse::<f32>().ok().filter(|r| (0.0..=1.0).contains(r))),
+    }
+}
+
+// Read a dataset: datagen's CSV, or PDN games such as selfplay prints. FENs
+// are read under `variant`, unless a PDN game names its own GameType.
+pub fn load_positions(path: &str, variant: Variant) -> Result<Vec<LabelledPosition>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("could not read '{}': {}", path, e))?;
+    parse_positions(&text, variant)
+}
+
+// PDN is recognised by its tag pairs
+pub fn parse_positions(text: &str, variant: Variant) -> Result<Vec<LabelledPosition>, String> {
+    if text.lines().any(|line| line.trim_start().starts_with('[')) {
+        parse_games(text, variant)
+    } else {
+        parse_lines(text, variant)
+    }
+}
+
+// One `fen;...;result` line per position. Only the first (FEN) and last
+// (result) fields are used, so extra columns such as a search score are
+// ignored.
+fn parse_lines(text: &str, variant: Variant) -> Result<Vec<LabelledPosition>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(line_no, line)| {
+            let fields: Vec<&str> = line.split(';').collect();
+            if fields.len() < 2 {
+                return Err(format!("line {}: expected 'fen;result'", line_no + 1));
+            }
+
+            let board = Board::from_variant_fen(variant, fields[0])
+                .map_err(|e| format!("line {}: {}", line_no + 1, e))?;
+            let result = parse_result(fields[fields.len() - 1])
+                .ok_or_else(|| format!("line {}: invalid result", line_no + 1))?;
+
+            Ok(LabelledPosition { board, result })
+        })
+        .collect()
+}
+
+// Every quiet position (one without a capture to make) of every finished
+// game, labelled with the game's result. The result comes from the Result
+// tag or the end of the move text; games without one are skipped.
+fn parse_games(text: &str, variant: Variant) -> Result<Vec<LabelledPosition>, String> {
+    let mut positions = Vec::new();
+
+    for (first_line, game) in split_games(text) {
+        let located = |e: script::ScriptError| format!("line {}: {}", first_line + e.line - 1, e.message);
+        let script = Script::parse_variant(variant, &game).map_err(located)?;
+
+        let tag = game.lines().find_map(|line| line.trim().strip_prefix("[Result "));
+        let result = match tag {
+            Some(value) => pdn_result(value.trim_end_matches(']').trim().trim_matches('"')),
+            None => game.split_whitespace().rfind(|w| !w.starts_with('[')).and_then(pdn_result),
+        };
+        let Some(result) = result else {
+            continue;
+        };
+
+        script.run(|_, board, _| {
+            if board.get_valid_moves().first().is_some_and(|m| m.captures.is_empty()) {
+                positions.push(LabelledPosition { board: board.clone(), result });
+            }
+        }).map_err(located)?;
+    }
+
+    Ok(positions)
+}
+
+// The games of a PDN file, each with the line it starts on. A tag pair after
+// some move text starts the next game.
+fn split_games(text: &str) -> Vec<(usize, String)> {
+    let mut games: Vec<(usize, String)> = Vec::new();
+    let mut in_moves = false;
+
+    for (line_no, line) in text.lines().enumerate() {
+        let content = line.trim();
+        let is_tag = content.starts_with('[');
+        if games.is_empty() || (is_tag && in_moves) {
+            games.push((line_no + 1, String::new()));
+            in_moves = false;
+        }
+        if !is_tag && !content.is_empty() && !content.starts_with(['#', ';']) {
+            in_moves = true;
+        }
+
+        let game = &mut games.last_mut().expect("a game was just started").1;
+        game.push_str(line);
+        game.push('\n');
+    }
+
+    games
+}
+
+// Map an eval bar score to an expected game result
+fn sigmoid(score: f32, k: f32) -> f32 {
+    1.0 / (1.0 + (-k * score).exp())
+}
+
+// Mean squared error between predicted and actual results
+pub fn error(positions: &[LabelledPosition], weights: &EvalWeights, k: f32) -> f32 {
+    if positions.is_empty() {
+        return 0.0;
+    }
+
+    let total: f32 = positions.par_iter()
+        .map(|p| {
+            let diff = p.result - sigmoid(bar_with(&p.board, weights), k);
+            diff * diff
+        })
+        .sum();
+
+    total / positions.len() as f32
+}
+
+// Find the scaling constant K that best fits the current weights, so the
+// tuner only has to move the weights relative to each other
+pub fn best_k(positions: &[LabelledPosition], weights: &EvalWeights) -> f32 {
+    (1..=300)
+        .map(|i| i as f32 * 0.01)
+        .map(|k| (k, error(positions, weights, k)))
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(k, _)| k)
+        .unwrap_or(1.0)
+}
+
+// Texel style local search: nudge every weight up or down by one step and
+// keep the change whenever the error drops, until a full pass improves nothing
+pub fn tune<F>(positions: &[LabelledPosition], initial: &EvalWeights, config: &TuneConfig, mut on_progress: F) -> EvalWeights
+where
+    F: FnMut(&TuneProgress),
+{
+    let mut weights = initial.clone();
+    let k = best_k(positions, &weights);
+    let mut best_error = error(positions, &weights, k);
+    let progress = |iteration, error| TuneProgress { iteration, positions: positions.len(), k, error };
+
+    on_progress(&progress(0, best_error));
+
+    for iteration in 1..=config.max_iterations {
+        let mut improved = false;
+
+        for name in EvalWeights::NAMES {
+            // Disabled terms don't change the error, so don't bother trying them
+            if !weights.is_active(name) {
+                continue;
+            }
+
+            for delta in [config.step, -config.step] {
+                let mut candidate = weights.clone();
+                if let Some(value) = candidate.get_mut(name) {
+                    *value += delta;
+                }
+
+                let candidate_error = error(positions, &candidate, k);
+                if candidate_error < best_error {
+                    weights = candidate;
+                    best_error = candidate_error;
+                    improved = true;
+                    break;
+                }
+            }
+        }
+
+        on_progress(&progress(iteration, best_error));
+
+        if !improved {
+            break;
+        }
+    }
+
+    weights
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pdn_games_label_their_quiet_positions() {
+        // The second game has no result and is skipped
+        let text = "[Event \"selfplay\"]\n[FEN \"W:W21-32:B1-12\"]\n[Result \"0-1\"]\n\n1. 22-18 11-15 2. 18x11 8x15 0-1\n\n\
+                    [Event \"selfplay\"]\n\n1. 22-18 *\n\n\
+                    [GameType \"20\"]\n\n1. 32-28 19-23 2. 28x19 14x23 2-0\n";
+        let positions = parse_positions(text, Variant::American).unwrap();
+
+        // Plies 0, 1 and 4 of each game; at plies 2 and 3 a capture is due
+        let fens: Vec<(String, f32)> = positions.iter().map(|p| (p.board.to_fen(), p.result)).collect();
+        assert_eq!(fens.len(), 6, "{:?}", fens);
+        assert!(positions[..3].iter().all(|p| p.board.variant == Variant::American && p.result == 0.0));
+        assert!(positions[3..].iter().all(|p| p.board.variant == Variant::International && p.result == 1.0));
+
+        let csv = "W:W21-32:B1-12;0.25;1/2-1/2\n";
+        assert_eq!(parse_positions(csv, Variant::American).unwrap()[0].result, 0.5);
+        let russian = parse_positions(csv, Variant::Russian).unwrap();
+        assert_eq!(russian[0].board.variant, Variant::Russian);
+
+        let bad = "[Event \"x\"]\n\n1. 22-18 12-11 1-0\n";
+        assert_eq!(parse_positions(bad, Variant::American).unwrap_err().split(':').next(), Some("line 3"));
+    }
+
+    #[test]
+    fn tuning_fits_the_weights_to_the_results() {
+        // A king beats a man here, so the tuner should value kings above
+        // the men they started out equal to
+        let text = "W:WK22:B10;1-0\nB:WK22:B10;1-0\nW:W22:BK10;0-1\nB:W22:BK10;0-1\n\
+                    W:WK18:B14;1-0\nW:W18:BK14;0-1\nW:W22:B10;1/2-1/2\nB:W23:B9;1/2-1/2\n";
+        let positions = parse_positions(text, Variant::American).unwrap();
+        let initial = EvalWeights { king: 1.0, ..EvalWeights::default() };
+
+        let mut reports = Vec::new();
+        let tuned = tune(&positions, &initial, &TuneConfig { step: 0.1, max_iterations: 20 }, |p| reports.push(*p));
+
+        let k = reports[0].k;
+        assert!(error(&positions, &tuned, k) < error(&positions, &initial, k));
+        assert!(tuned.king > tuned.pawn, "{}", tuned);
+        assert_eq!(reports[0].error, error(&positions, &initial, k));
+        assert!(reports.windows(2).all(|w| w[1].error <= w[0].error), "{:?}", reports);
+    }
+}
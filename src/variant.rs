@@ -0,0 +1,124 @@
+use std::fmt;
+use crate::rules::{self, Rules};
+
+// The games we know the rules of; the rules themselves are in rules.rs
+
+// Squares on the largest supported board; boards store this many and use
+// the first num_squares()
+pub const MAX_SQUARES: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Variant {
+    #[default]
+    American,
+    International,
+    Russian,
+    Brazilian,
+    Italian,
+    Spanish,
+    Pool,
+    Czech,
+    Giveaway,
+}
+
+impl Variant {
+    pub const ALL: [Variant; 9] = [
+        Variant::American, Variant::International, Variant::Russian, Variant::Brazilian,
+        Variant::Italian, Variant::Spanish, Variant::Pool, Variant::Czech, Variant::Giveaway,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "american" | "english" | "checkers" => Some(Variant::American),
+            "international" | "intl" | "10x10" => Some(Variant::International),
+            "russian" => Some(Variant::Russian),
+            "brazilian" => Some(Variant::Brazilian),
+            "italian" => Some(Variant::Italian),
+            "spanish" => Some(Variant::Spanish),
+            "pool" => Some(Variant::Pool),
+            "czech" => Some(Variant::Czech),
+            "giveaway" | "suicide" | "antidraughts" => Some(Variant::Giveaway),
+            _ => None,
+        }
+    }
+
+    // The PDN GameType tag value, e.g. [GameType "20"]
+    pub fn from_pdn(game_type: &str) -> Option<Self> {
+        match game_type.split(',').next()?.trim() {
+            "21" => Some(Variant::American),
+            "20" => Some(Variant::International),
+            "22" => Some(Variant::Italian),
+            "23" => Some(Variant::Pool),
+            "24" => Some(Variant::Spanish),
+            "25" => Some(Variant::Russian),
+            "26" => Some(Variant::Brazilian),
+            "29" => Some(Variant::Czech),
+            _ => None,
+        }
+    }
+
+    // The GameType tag value of the variants PDN has a number for
+    pub fn pdn_game_type(&self) -> Option<&'static str> {
+        match self {
+            Variant::American => Some("21"),
+            Variant::International => Some("20"),
+            Variant::Italian => Some("22"),
+            Variant::Pool => Some("23"),
+            Variant::Spanish => Some("24"),
+            Variant::Russian => Some("25"),
+            Variant::Brazilian => Some("26"),
+            Variant::Czech => Some("29"),
+            Variant::Giveaway => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Variant::American => "american",
+            Variant::International => "international",
+            Variant::Russian => "russian",
+            Variant::Brazilian => "brazilian",
+            Variant::Italian => "italian",
+            Variant::Spanish => "spanish",
+            Variant::Pool => "pool",
+            Variant::Czech => "czech",
+            Variant::Giveaway => "giveaway",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Variant::American => "American Checkers",
+            Variant::International => "International Draughts",
+            Variant::Russian => "Russian Draughts",
+            Variant::Brazilian => "Brazilian Draughts",
+            Variant::Italian => "Italian Checkers",
+            Variant::Spanish => "Spanish Draughts",
+            Variant::Pool => "Pool Checkers",
+            Variant::Czech => "Czech Draughts",
+            Variant::Giveaway => "Giveaway Checkers",
+        }
+    }
+
+    pub fn rules(&self) -> &'static dyn Rules {
+        match self {
+            Variant::American => &rules::American,
+            Variant::International => &rules::International,
+            Variant::Russian => &rules::Russian,
+            Variant::Brazilian => &rules::Brazilian,
+            Variant::Italian => &rules::Italian,
+            Variant::Spanish => &rules::Spanish,
+            Variant::Pool => &rules::Pool,
+            Variant::Czech => &rules::Czech,
+            Variant::Giveaway => &rules::Giveaway,
+        }
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
@@ -0,0 +1,289 @@
+use std::fmt;
+use std::fs;
+use std::io;
+
+// Positional terms that can be switched on and off on their own, so their
+// value can be measured in matches. They all start off, so bar keeps its
+// original behaviour until a weights file turns them on.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct EvalFeatures {
+    pub back_rank: bool,
+    pub runaway: bool,
+    pub trapped_king: bool,
+    pub mobility: bool,
+    pub tempo: bool,
+    pub dog_hole: bool,
+    pub exposed: bool,
+    pub opposition: bool,
+}
+
+impl EvalFeatures {
+    pub const NAMES: [&'static str; 8] = [
+        "back_rank",
+        "runaway",
+        "trapped_king",
+        "mobility",
+        "tempo",
+        "dog_hole",
+        "exposed",
+        "opposition",
+    ];
+
+    pub fn all() -> Self {
+        EvalFeatures {
+            back_rank: true,
+            runaway: true,
+            trapped_king: true,
+            mobility: true,
+            tempo: true,
+            dog_hole: true,
+            exposed: true,
+            opposition: true,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<bool> {
+        match name {
+            "back_rank" => Some(self.back_rank),
+            "runaway" => Some(self.runaway),
+            "trapped_king" => Some(self.trapped_king),
+            "mobility" => Some(self.mobility),
+            "tempo" => Some(self.tempo),
+            "dog_hole" => Some(self.dog_hole),
+            "exposed" => Some(self.exposed),
+            "opposition" => Some(self.opposition),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "back_rank" => Some(&mut self.back_rank),
+            "runaway" => Some(&mut self.runaway),
+            "trapped_king" => Some(&mut self.trapped_king),
+            "mobility" => Some(&mut self.mobility),
+            "tempo" => Some(&mut self.tempo),
+            "dog_hole" => Some(&mut self.dog_hole),
+            "exposed" => Some(&mut self.exposed),
+            "opposition" => Some(&mut self.opposition),
+            _ => None,
+        }
+    }
+}
+
+// Tunable weights used by the eval bar (see ab_ai::bar_with)
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalWeights {
+    pub pawn: f32,          // Value of a man
+    pub king: f32,          // Value of a king
+    pub edge: f32,          // Penalty for pieces on columns A/H
+    pub advance: f32,       // Bonus scale for men advancing towards promotion
+    pub promotion: f32,     // Bonus for a man standing on the promotion row
+    pub king_center: f32,   // Bonus for a king on the middle rows
+    pub back_rank: f32,     // Bonus per man still guarding a bridge square
+    pub runaway: f32,       // Bonus for a man with a free path to promotion
+    pub trapped_king: f32,  // Penalty for a king that cannot move or jump
+    pub mobility: f32,      // Bonus per available non-capturing step
+    pub tempo: f32,         // Bonus per row the men have advanced in total
+    pub dog_hole: f32,      // Penalty for a man stuck in the dog-hole
+    pub exposed: f32,       // Penalty per piece the opponent could jump
+    pub opposition: f32,    // Bonus for having "the move" in the endgame
+    pub features: EvalFeatures,
+}
+
+impl Default for EvalWeights {
+    // The hand-tuned values bar has always used, plus starting values for
+    // the positional terms (which are switched off)
+    fn default() -> Self {
+        EvalWeights {
+            pawn: 1.0,
+            king: 2.5,
+            edge: 0.25,
+            advance: 0.5,
+            promotion: 2.5,
+            king_center: 1.5,
+            back_rank: 0.2,
+            runaway: 1.0,
+            trapped_king: 0.5,
+            mobility: 0.05,
+            tempo: 0.02,
+            dog_hole: 0.3,
+            exposed: 0.3,
+            opposition: 0.25,
+            features: EvalFeatures::default(),
+        }
+    }
+}
+
+impl EvalWeights {
+    // Names of every tunable parameter, in file order
+    pub const NAMES: [&'static str; 14] = [
+        "pawn",
+        "king",
+        "edge",
+        "advance",
+        "promotion",
+        "king_center",
+        "back_rank",
+        "runaway",
+        "trapped_king",
+        "mobility",
+        "tempo",
+        "dog_hole",
+        "exposed",
+        "opposition",
+    ];
+
+    // The default weights with every positional term switched on
+    pub fn all_terms() -> Self {
+        EvalWeights {
+            features: EvalFeatures::all(),
+            ..EvalWeights::default()
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<f32> {
+        match name {
+            "pawn" => Some(self.pawn),
+            "king" => Some(self.king),
+            "edge" => Some(self.edge),
+            "advance" => Some(self.advance),
+            "promotion" => Some(self.promotion),
+            "king_center" => Some(self.king_center),
+            "back_rank" => Some(self.back_rank),
+            "runaway" => Some(self.runaway),
+            "trapped_king" => Some(self.trapped_king),
+            "mobility" => Some(self.mobility),
+            "tempo" => Some(self.tempo),
+            "dog_hole" => Some(self.dog_hole),
+            "exposed" => Some(self.exposed),
+            "opposition" => Some(self.opposition),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut f32> {
+        match name {
+            "pawn" => Some(&mut self.pawn),
+            "king" => Some(&mut self.king),
+            "edge" => Some(&mut self.edge),
+            "advance" => Some(&mut self.advance),
+            "promotion" => Some(&mut self.promotion),
+            "king_center" => Some(&mut self.king_center),
+            "back_rank" => Some(&mut self.back_rank),
+            "runaway" => Some(&mut self.runaway),
+            "trapped_king" => Some(&mut self.trapped_king),
+            "mobility" => Some(&mut self.mobility),
+            "tempo" => Some(&mut self.tempo),
+            "dog_hole" => Some(&mut self.dog_hole),
+            "exposed" => Some(&mut self.exposed),
+            "opposition" => Some(&mut self.opposition),
+            _ => None,
+        }
+    }
+
+    // Whether a weight currently affects the evaluation. The base terms
+    // have no switch and are always on.
+    pub fn is_active(&self, name: &str) -> bool {
+        self.features.get(name).unwrap_or(true)
+    }
+
+    // Parse a weights file made of `name = value` lines, plus
+    // `name.enabled = true|false` lines for the switchable terms. Missing
+    // names keep their default value, '#' starts a comment.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut weights = EvalWeights::default();
+
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (name, value) = line.split_once('=')
+                .ok_or_else(|| format!("line {}: expected 'name = value'", line_no + 1))?;
+            let (name, value) = (name.trim(), value.trim());
+
+            if let Some(term) = name.strip_suffix(".enabled") {
+                let enabled: bool = value.parse()
+                    .map_err(|_| format!("line {}: expected true or false, got '{}'", line_no + 1, value))?;
+
+                let slot = weights.features.get_mut(term)
+                    .ok_or_else(|| format!("line {}: unknown term '{}'", line_no + 1, term))?;
+                *slot = enabled;
+                continue;
+            }
+
+            let value: f32 = value.parse()
+                .map_err(|_| format!("line {}: invalid number '{}'", line_no + 1, value))?;
+
+            let slot = weights.get_mut(name)
+                .ok_or_else(|| format!("line {}: unknown weight '{}'", line_no + 1, name))?;
+            *slot = value;
+        }
+
+        Ok(weights)
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("could not read '{}': {}", path, e))?;
+        Self::parse(&text)
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+}
+
+impl fmt::Display for EvalWeights {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for name in Self::NAMES {
+            // Every name in NAMES is known to get()
+            writeln!(f, "{} = {:.4}", name, self.get(name).unwrap_or_default())?;
+        }
+        for term in EvalFeatures::NAMES {
+            writeln!(f, "{}.enabled = {}", term, self.features.get(term).unwrap_or_default())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weights_files_round_trip() {
+        let mut weights = EvalWeights::all_terms();
+        weights.king = 3.125;
+        weights.mobility = -0.05;
+        weights.features.tempo = false;
+
+        assert_eq!(EvalWeights::parse(&weights.to_string()).unwrap(), weights);
+
+        let path = std::env::temp_dir().join(format!("checkers-weights-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        weights.save(path).unwrap();
+        let loaded = EvalWeights::load(path);
+        let _ = fs::remove_file(path);
+        assert_eq!(loaded.unwrap(), weights);
+    }
+
+    #[test]
+    fn partial_files_keep_the_defaults() {
+        let weights = EvalWeights::parse("# only two terms\nking = 3\n\nrunaway.enabled = true  # switched on\n").unwrap();
+        assert_eq!(weights, EvalWeights {
+            king: 3.0,
+            features: EvalFeatures { runaway: true, ..EvalFeatures::default() },
+            ..EvalWeights::default()
+        });
+
+        assert_eq!(EvalWeights::parse("king 3").unwrap_err(), "line 1: expected 'name = value'");
+        assert_eq!(EvalWeights::parse("\nqueen = 9").unwrap_err(), "line 2: unknown weight 'queen'");
+        assert_eq!(EvalWeights::parse("king = x").unwrap_err(), "line 1: invalid number 'x'");
+        assert_eq!(EvalWeights::parse("pawn.enabled = true").unwrap_err(), "line 1: unknown term 'pawn'");
+        assert_eq!(EvalWeights::parse("tempo.enabled = 1").unwrap_err(), "line 1: expected true or false, got '1'");
+        assert!(EvalWeights::load("/nonexistent/weights.txt").unwrap_err().starts_with("could not read"));
+    }
+}
@@ -0,0 +1,115 @@
+// Pins the serde JSON schema documented in the README: each type is written
+// to a fixed string and read back. Run with `cargo test --features serde`.
+#![cfg(feature = "serde")]
+
+use std::time::Duration;
+use checkers_bot::board::{Board, Color};
+use checkers_bot::mv::{Move, SquareList};
+use checkers_bot::player::{GameRecord, GameResult};
+use checkers_bot::search::SearchResult;
+use checkers_bot::variant::Variant;
+
+// 22x15 over 18, in 0-based squares
+fn capture() -> Move {
+    Move::with_path(21, 14, SquareList::from_slice(&[17]).unwrap(), SquareList::from_slice(&[21, 14]).unwrap())
+}
+
+fn search_result(score: f32) -> SearchResult {
+    SearchResult {
+        best_move: Some(Move::new(21, 17)),
+        score,
+        depth: 8,
+        nodes: 5741,
+        pv: vec![Move::new(21, 17), Move::new(10, 14)],
+        elapsed: Duration::from_millis(12),
+    }
+}
+
+#[test]
+fn board_is_its_variant_and_fen() {
+    let json = serde_json::to_string(&Board::new()).unwrap();
+    assert_eq!(json, r#"{"variant":"american","fen":"W:W21,22,23,24,25,26,27,28,29,30,31,32:B1,2,3,4,5,6,7,8,9,10,11,12"}"#);
+
+    let board: Board = serde_json::from_str(&json).unwrap();
+    assert_eq!(board.to_fen(), Board::new().to_fen());
+    assert_eq!(board.variant, Variant::American);
+
+    let board: Board = serde_json::from_str(r#"{"fen":"B:W21-32:B1-12"}"#).unwrap();
+    assert_eq!(board.variant, Variant::American);
+    assert_eq!(board.turn, Color::Black);
+
+    let international = Board::for_variant(Variant::International);
+    let json = serde_json::to_string(&international).unwrap();
+    assert!(json.starts_with(r#"{"variant":"international","fen":"#), "{}", json);
+    let board: Board = serde_json::from_str(&json).unwrap();
+    assert_eq!(board.to_fen(), international.to_fen());
+
+    assert!(serde_json::from_str::<Board>(r#"{"fen":"not a fen"}"#).is_err());
+}
+
+#[test]
+fn moves_use_square_numbers_from_one() {
+    let json = serde_json::to_string(&capture()).unwrap();
+    assert_eq!(json, r#"{"from":22,"to":15,"path":[22,15],"captures":[18]}"#);
+    assert_eq!(serde_json::from_str::<Move>(&json).unwrap(), capture());
+
+    let json = serde_json::to_string(&Move::new(21, 17)).unwrap();
+    assert_eq!(json, r#"{"from":22,"to":18,"path":[22,18],"captures":[]}"#);
+    assert_eq!(serde_json::from_str::<Move>(r#"{"from":22,"to":18}"#).unwrap(), Move::new(21, 17));
+
+    assert!(serde_json::from_str::<Move>(r#"{"from":0,"to":18}"#).is_err());
+    assert!(serde_json::from_str::<Move>(r#"{"from":22,"to":18,"path":[22,17]}"#).is_err());
+}
+
+#[test]
+fn search_results_write_wins_and_milliseconds() {
+    let json = serde_json::to_string(&search_result(0.25)).unwrap();
+    assert_eq!(
+        json,
+        concat!(
+            r#"{"best_move":{"from":22,"to":18,"path":[22,18],"captures":[]},"score":0.25,"depth":8,"nodes":5741,"#,
+            r#""pv":[{"from":22,"to":18,"path":[22,18],"captures":[]},{"from":11,"to":15,"path":[11,15],"captures":[]}],"#,
+            r#""time_ms":12}"#,
+        )
+    );
+
+    let result: SearchResult = serde_json::from_str(&json).unwrap();
+    assert_eq!(result.best_move, Some(Move::new(21, 17)));
+    assert_eq!(result.score, 0.25);
+    assert_eq!((result.depth, result.nodes), (8, 5741));
+    assert_eq!(result.pv, vec![Move::new(21, 17), Move::new(10, 14)]);
+    assert_eq!(result.elapsed, Duration::from_millis(12));
+
+    for (score, text) in [(f32::INFINITY, r#""score":"win""#), (f32::NEG_INFINITY, r#""score":"loss""#)] {
+        let json = serde_json::to_string(&search_result(score)).unwrap();
+        assert!(json.contains(text), "{}", json);
+        assert_eq!(serde_json::from_str::<SearchResult>(&json).unwrap().score, score);
+    }
+
+    let json = serde_json::to_string(&SearchResult { best_move: None, pv: Vec::new(), ..search_result(0.0) }).unwrap();
+    assert!(json.starts_with(r#"{"best_move":null,"score":0.0,"#), "{}", json);
+    assert!(serde_json::from_str::<SearchResult>(&json.replace("0.0", "\"draw\"")).is_err());
+}
+
+#[test]
+fn game_records_hold_start_moves_and_result() {
+    let record = GameRecord { start: Board::new(), moves: vec![Move::new(21, 17)], result: GameResult::Win(Color::Red) };
+    let json = serde_json::to_string(&record).unwrap();
+    assert_eq!(
+        json,
+        concat!(
+            r#"{"start":{"variant":"american","fen":"W:W21,22,23,24,25,26,27,28,29,30,31,32:B1,2,3,4,5,6,7,8,9,10,11,12"},"#,
+            r#""moves":[{"from":22,"to":18,"path":[22,18],"captures":[]}],"result":{"win":"red"}}"#,
+        )
+    );
+
+    let read: GameRecord = serde_json::from_str(&json).unwrap();
+    assert_eq!(read.start.to_fen(), record.start.to_fen());
+    assert_eq!(read.moves, record.moves);
+    assert_eq!(read.result, record.result);
+
+    let draw = GameRecord { result: GameResult::Draw, ..record };
+    let json = serde_json::to_string(&draw).unwrap();
+    assert!(json.ends_with(r#""result":"draw"}"#), "{}", json);
+    assert_eq!(serde_json::from_str::<GameRecord>(&json).unwrap().result, GameResult::Draw);
+}
@@ -0,0 +1,27 @@
+// Counts heap allocations made during an alpha-beta search. It lives in its
+// own test binary so the counting allocator sees nothing but this search.
+
+use checkers_bot::ab_ai;
+use checkers_bot::allocations::{self, Counting};
+use checkers_bot::board::Board;
+use checkers_bot::evaluator::Heuristic;
+use checkers_bot::search::SearchLimits;
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+#[test]
+fn search_does_not_allocate_per_node() {
+    let board = Board::new();
+    let evaluator = Heuristic::default();
+    let limits = SearchLimits { depth: Some(8), ..SearchLimits::default() };
+
+    let before = allocations::count();
+    let result = ab_ai::search(&board, &limits, &evaluator);
+    let allocated = allocations::count() - before;
+
+    // Move lists and the principal variation grow once per ply and are then
+    // reused, so the count depends on the depth rather than the tree size
+    assert!(result.nodes > 10_000, "searched only {} nodes", result.nodes);
+    assert!(allocated < 200, "{} allocations for {} nodes", allocated, result.nodes);
+}